@@ -0,0 +1,164 @@
+use std::fmt;
+
+/// An RGBA color with every channel stored in the range 0 to 1.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Color {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+    pub a: f64,
+}
+
+fn clamp(n: f64) -> f64 {
+    n.clamp(0.0, 1.0)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+impl Color {
+    pub fn new(r: f64, g: f64, b: f64, a: f64) -> Color {
+        Color {
+            r: clamp(r),
+            g: clamp(g),
+            b: clamp(b),
+            a: clamp(a),
+        }
+    }
+
+    /// Parse the digits of a `#rgb`, `#rrggbb` or `#rrggbbaa` color.
+    pub fn from_hex(hex: &str) -> Option<Color> {
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        let channel = |i: usize, len: usize| {
+            let digits = &hex[i * len..(i + 1) * len];
+            let n = u8::from_str_radix(digits, 16).unwrap();
+            if len == 1 {
+                f64::from(n * 17) / 255.0
+            } else {
+                f64::from(n) / 255.0
+            }
+        };
+
+        match hex.len() {
+            3 => Some(Color::new(channel(0, 1), channel(1, 1), channel(2, 1), 1.0)),
+            6 => Some(Color::new(channel(0, 2), channel(1, 2), channel(2, 2), 1.0)),
+            8 => Some(Color::new(
+                channel(0, 2),
+                channel(1, 2),
+                channel(2, 2),
+                channel(3, 2),
+            )),
+            _ => None,
+        }
+    }
+
+    /// Create a color from a hue in degrees and saturation, lightness and
+    /// alpha between 0 and 1.
+    pub fn from_hsl(h: f64, s: f64, l: f64, a: f64) -> Color {
+        let h = (h % 360.0 + 360.0) % 360.0 / 360.0;
+        let (s, l) = (clamp(s), clamp(l));
+
+        if s == 0.0 {
+            return Color::new(l, l, l, a);
+        }
+
+        let q = if l < 0.5 {
+            l * (1.0 + s)
+        } else {
+            l + s - l * s
+        };
+        let p = 2.0 * l - q;
+
+        let hue = |t: f64| {
+            let t = (t + 1.0) % 1.0;
+            if t < 1.0 / 6.0 {
+                p + (q - p) * 6.0 * t
+            } else if t < 1.0 / 2.0 {
+                q
+            } else if t < 2.0 / 3.0 {
+                p + (q - p) * (2.0 / 3.0 - t) * 6.0
+            } else {
+                p
+            }
+        };
+
+        Color::new(hue(h + 1.0 / 3.0), hue(h), hue(h - 1.0 / 3.0), a)
+    }
+
+    /// The hue in degrees and the saturation and lightness between 0 and 1.
+    pub fn to_hsl(self) -> (f64, f64, f64) {
+        let max = self.r.max(self.g).max(self.b);
+        let min = self.r.min(self.g).min(self.b);
+        let l = (max + min) / 2.0;
+
+        if max == min {
+            return (0.0, 0.0, l);
+        }
+
+        let d = max - min;
+        let s = if l > 0.5 {
+            d / (2.0 - max - min)
+        } else {
+            d / (max + min)
+        };
+
+        let h = if max == self.r {
+            (self.g - self.b) / d + if self.g < self.b { 6.0 } else { 0.0 }
+        } else if max == self.g {
+            (self.b - self.r) / d + 2.0
+        } else {
+            (self.r - self.g) / d + 4.0
+        };
+
+        (h * 60.0, s, l)
+    }
+
+    pub fn mix(self, other: Color, t: f64) -> Color {
+        let t = clamp(t);
+        Color::new(
+            lerp(self.r, other.r, t),
+            lerp(self.g, other.g, t),
+            lerp(self.b, other.b, t),
+            lerp(self.a, other.a, t),
+        )
+    }
+
+    /// Shift the lightness by `amount`, which can be negative to darken.
+    pub fn lighten(self, amount: f64) -> Color {
+        let (h, s, l) = self.to_hsl();
+        Color::from_hsl(h, s, l + amount, self.a)
+    }
+
+    pub fn with_alpha(self, a: f64) -> Color {
+        Color::new(self.r, self.g, self.b, a)
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let channel = |c: f64| (c * 255.0).round() as u8;
+
+        if self.a >= 1.0 {
+            write!(
+                f,
+                "#{:02x}{:02x}{:02x}",
+                channel(self.r),
+                channel(self.g),
+                channel(self.b)
+            )
+        } else {
+            write!(
+                f,
+                "rgba({}, {}, {}, {})",
+                channel(self.r),
+                channel(self.g),
+                channel(self.b),
+                (self.a * 1000.0).round() / 1000.0
+            )
+        }
+    }
+}
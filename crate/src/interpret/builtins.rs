@@ -0,0 +1,490 @@
+use crate::color::Color;
+use crate::lexer::Unit;
+
+use super::error::EvalErrorType::*;
+use super::*;
+
+/// The evaluated arguments to a native function along with where each one
+/// came from, so type errors can point at the offending argument.
+pub struct Args {
+    values: Vec<Value>,
+    positions: Vec<Pos>,
+}
+
+impl Args {
+    pub fn new() -> Self {
+        Args {
+            values: vec![],
+            positions: vec![],
+        }
+    }
+
+    pub fn push(&mut self, value: Value, pos: Pos) {
+        self.values.push(value);
+        self.positions.push(pos);
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn value(&self, i: usize) -> Value {
+        self.values[i].clone()
+    }
+
+    pub fn arg_pos(&self, i: usize) -> Pos {
+        self.positions[i]
+    }
+
+    pub fn number(&self, i: usize) -> EvalResult<(f64, Option<Unit>)> {
+        get_number(self.value(i), self.arg_pos(i))
+    }
+
+    pub fn color(&self, i: usize) -> EvalResult<Color> {
+        match self.value(i) {
+            Value::Color(c) => Ok(c),
+            v => eval_error(TypeMismatch("color".to_owned(), v.name()), self.arg_pos(i)),
+        }
+    }
+
+    /// A number between 0 and 1, which can also be given as a percentage.
+    pub fn fraction(&self, i: usize) -> EvalResult<f64> {
+        match self.number(i)? {
+            (n, None) => Ok(n),
+            (n, Some(Unit::Percent)) => Ok(n / 100.0),
+            (_, unit) => eval_error(
+                TypeMismatch("number or percentage".to_owned(), unit_name(unit)),
+                self.arg_pos(i),
+            ),
+        }
+    }
+
+    pub fn list(&self, i: usize) -> EvalResult<Vec<Value>> {
+        match self.value(i) {
+            Value::List(values) => Ok(values),
+            v => eval_error(TypeMismatch("list".to_owned(), v.name()), self.arg_pos(i)),
+        }
+    }
+
+    pub fn vec2(&self, i: usize) -> EvalResult<(f64, f64, Option<Unit>)> {
+        match self.value(i) {
+            Value::Vec2(x, y, unit) => Ok((x, y, unit)),
+            v => eval_error(TypeMismatch("vector".to_owned(), v.name()), self.arg_pos(i)),
+        }
+    }
+
+    /// The name of a user or native function, as given by `map(xs, double)`.
+    pub fn function(&self, i: usize) -> EvalResult<String> {
+        match self.value(i) {
+            Value::Function(name) => Ok(name),
+            v => eval_error(
+                TypeMismatch("function".to_owned(), v.name()),
+                self.arg_pos(i),
+            ),
+        }
+    }
+
+    /// A number without a unit.
+    pub fn plain(&self, i: usize) -> EvalResult<f64> {
+        match self.number(i)? {
+            (n, None) => Ok(n),
+            (_, unit) => eval_error(
+                TypeMismatch(unit_name(None), unit_name(unit)),
+                self.arg_pos(i),
+            ),
+        }
+    }
+
+    /// Several numbers converted to a common unit, like the operands of `+`.
+    pub fn unified(&self, indices: &[usize]) -> EvalResult<(Vec<f64>, Option<Unit>)> {
+        let mut unit = None;
+        let mut values = vec![];
+
+        for &i in indices.iter() {
+            let (_, n, u) = unify_units((0.0, unit), self.number(i)?, self.arg_pos(i))?;
+            values.push(n);
+            unit = u;
+        }
+
+        Ok((values, unit))
+    }
+
+    /// An angle in radians. Plain numbers are taken to already be radians.
+    pub fn radians(&self, i: usize) -> EvalResult<f64> {
+        match self.number(i)? {
+            (n, None) => Ok(n),
+            (n, Some(unit)) if unit.is_angle() => Ok(to_radians(n, unit)),
+            (_, unit) => eval_error(
+                TypeMismatch("angle".to_owned(), unit_name(unit)),
+                self.arg_pos(i),
+            ),
+        }
+    }
+
+    /// An angle in degrees. Plain numbers are taken to already be degrees.
+    pub fn degrees(&self, i: usize) -> EvalResult<f64> {
+        match self.number(i)? {
+            (n, None) => Ok(n),
+            (n, Some(unit)) if unit.is_angle() => Ok(to_radians(n, unit).to_degrees()),
+            (_, unit) => eval_error(
+                TypeMismatch("angle".to_owned(), unit_name(unit)),
+                self.arg_pos(i),
+            ),
+        }
+    }
+}
+
+// a color channel from 0 to 255 or a percentage
+fn channel(args: &Args, i: usize) -> EvalResult<f64> {
+    match args.number(i)? {
+        (n, Some(Unit::Percent)) => Ok(n / 100.0),
+        (n, None) => Ok(n / 255.0),
+        (_, unit) => eval_error(
+            TypeMismatch("number or percentage".to_owned(), unit_name(unit)),
+            args.arg_pos(i),
+        ),
+    }
+}
+
+fn optional_alpha(args: &Args, i: usize) -> EvalResult<f64> {
+    if args.len() > i {
+        args.fraction(i)
+    } else {
+        Ok(1.0)
+    }
+}
+
+pub fn rgb(args: &Args) -> EvalResult<Value> {
+    Ok(Value::Color(Color::new(
+        channel(args, 0)?,
+        channel(args, 1)?,
+        channel(args, 2)?,
+        optional_alpha(args, 3)?,
+    )))
+}
+
+pub fn hsl(args: &Args) -> EvalResult<Value> {
+    Ok(Value::Color(Color::from_hsl(
+        args.degrees(0)?,
+        args.fraction(1)?,
+        args.fraction(2)?,
+        optional_alpha(args, 3)?,
+    )))
+}
+
+pub fn mix(args: &Args) -> EvalResult<Value> {
+    let t = if args.len() > 2 {
+        args.fraction(2)?
+    } else {
+        0.5
+    };
+
+    Ok(Value::Color(args.color(0)?.mix(args.color(1)?, t)))
+}
+
+pub fn lighten(args: &Args) -> EvalResult<Value> {
+    Ok(Value::Color(args.color(0)?.lighten(args.fraction(1)?)))
+}
+
+pub fn darken(args: &Args) -> EvalResult<Value> {
+    Ok(Value::Color(args.color(0)?.lighten(-args.fraction(1)?)))
+}
+
+pub fn alpha(args: &Args) -> EvalResult<Value> {
+    Ok(Value::Color(args.color(0)?.with_alpha(args.fraction(1)?)))
+}
+
+pub fn sin(args: &Args) -> EvalResult<Value> {
+    Ok(Value::from_number(args.radians(0)?.sin()))
+}
+
+pub fn cos(args: &Args) -> EvalResult<Value> {
+    Ok(Value::from_number(args.radians(0)?.cos()))
+}
+
+pub fn tan(args: &Args) -> EvalResult<Value> {
+    Ok(Value::from_number(args.radians(0)?.tan()))
+}
+
+// `atan2(y, x)`, the angle in radians of the point (x, y), with y first as in C
+pub fn atan2(args: &Args) -> EvalResult<Value> {
+    let (values, _) = args.unified(&[0, 1])?;
+    Ok(Value::from_number(values[0].atan2(values[1])))
+}
+
+pub fn sqrt(args: &Args) -> EvalResult<Value> {
+    Ok(Value::from_number(args.plain(0)?.sqrt()))
+}
+
+pub fn pow(args: &Args) -> EvalResult<Value> {
+    Ok(Value::from_number(args.plain(0)?.powf(args.plain(1)?)))
+}
+
+fn map_number(args: &Args, f: fn(f64) -> f64) -> EvalResult<Value> {
+    let (n, unit) = args.number(0)?;
+    Ok(Value::Number(f(n), unit))
+}
+
+pub fn abs(args: &Args) -> EvalResult<Value> {
+    map_number(args, f64::abs)
+}
+
+pub fn floor(args: &Args) -> EvalResult<Value> {
+    map_number(args, f64::floor)
+}
+
+pub fn ceil(args: &Args) -> EvalResult<Value> {
+    map_number(args, f64::ceil)
+}
+
+pub fn round(args: &Args) -> EvalResult<Value> {
+    map_number(args, f64::round)
+}
+
+pub fn min(args: &Args) -> EvalResult<Value> {
+    let indices: Vec<usize> = (0..args.len()).collect();
+    let (values, unit) = args.unified(&indices)?;
+    Ok(Value::Number(
+        values.into_iter().fold(f64::INFINITY, f64::min),
+        unit,
+    ))
+}
+
+pub fn max(args: &Args) -> EvalResult<Value> {
+    let indices: Vec<usize> = (0..args.len()).collect();
+    let (values, unit) = args.unified(&indices)?;
+    Ok(Value::Number(
+        values.into_iter().fold(f64::NEG_INFINITY, f64::max),
+        unit,
+    ))
+}
+
+// clamp(x, lo, hi)
+pub fn clamp(args: &Args) -> EvalResult<Value> {
+    let (values, unit) = args.unified(&[0, 1, 2])?;
+    Ok(Value::Number(values[0].max(values[1]).min(values[2]), unit))
+}
+
+// lerp(a, b, t) where t of 0 gives a and 1 gives b
+pub fn lerp(args: &Args) -> EvalResult<Value> {
+    let (values, unit) = args.unified(&[0, 1])?;
+    let t = args.fraction(2)?;
+    Ok(Value::Number(values[0] + (values[1] - values[0]) * t, unit))
+}
+
+// map_range(x, in_min, in_max, out_min, out_max)
+pub fn map_range(args: &Args) -> EvalResult<Value> {
+    let (input, _) = args.unified(&[0, 1, 2])?;
+    let (output, unit) = args.unified(&[3, 4])?;
+    let t = (input[0] - input[1]) / (input[2] - input[1]);
+    Ok(Value::Number(output[0] + (output[1] - output[0]) * t, unit))
+}
+
+pub fn vec(args: &Args) -> EvalResult<Value> {
+    let (values, unit) = args.unified(&[0, 1])?;
+    Ok(Value::Vec2(values[0], values[1], unit))
+}
+
+pub fn length(args: &Args) -> EvalResult<Value> {
+    let (x, y, unit) = args.vec2(0)?;
+    Ok(Value::Number(x.hypot(y), unit))
+}
+
+// normalize(v) is the vector in the same direction with a length of 1
+pub fn normalize(args: &Args) -> EvalResult<Value> {
+    let (x, y, _) = args.vec2(0)?;
+    let length = x.hypot(y);
+    Ok(Value::Vec2(x / length, y / length, None))
+}
+
+// dot(a, b) where at most one of the vectors can have a unit, like `*`
+pub fn dot(args: &Args) -> EvalResult<Value> {
+    let (x1, y1, u1) = args.vec2(0)?;
+    let (x2, y2, u2) = args.vec2(1)?;
+
+    match (u1, u2) {
+        (Some(_), Some(_)) => eval_error(
+            TypeMismatch(unit_name(None), unit_name(u2)),
+            args.arg_pos(1),
+        ),
+        _ => Ok(Value::Number(x1 * x2 + y1 * y2, u1.or(u2))),
+    }
+}
+
+// rotate(v, angle) turns the vector around the origin
+pub fn rotate(args: &Args) -> EvalResult<Value> {
+    let (x, y, unit) = args.vec2(0)?;
+    let (sin, cos) = args.radians(1)?.sin_cos();
+    Ok(Value::Vec2(x * cos - y * sin, x * sin + y * cos, unit))
+}
+
+// polar(r, theta) is the vector with length r at the angle theta
+pub fn polar(args: &Args) -> EvalResult<Value> {
+    let (r, unit) = args.number(0)?;
+    let (sin, cos) = args.radians(1)?.sin_cos();
+    Ok(Value::Vec2(r * cos, r * sin, unit))
+}
+
+pub fn random(_args: &Args, ctx: &mut Context) -> EvalResult<Value> {
+    Ok(Value::from_number(ctx.random().next_f64()))
+}
+
+// random_range(a, b) is a number from a up to but not including b
+pub fn random_range(args: &Args, ctx: &mut Context) -> EvalResult<Value> {
+    let (values, unit) = args.unified(&[0, 1])?;
+    let t = ctx.random().next_f64();
+    Ok(Value::Number(values[0] + (values[1] - values[0]) * t, unit))
+}
+
+// random_int(a, b) is a whole number from a up to but not including b, like
+// the values of the range a..b
+pub fn random_int(args: &Args, ctx: &mut Context) -> EvalResult<Value> {
+    let (values, unit) = args.unified(&[0, 1])?;
+    let (start, end) = (values[0].ceil(), values[1].ceil());
+
+    if end <= start {
+        return eval_error(EmptyRange, args.arg_pos(1));
+    }
+
+    let n = ctx.random().below((end - start) as usize);
+    Ok(Value::Number(start + n as f64, unit))
+}
+
+pub fn choose(args: &Args, ctx: &mut Context) -> EvalResult<Value> {
+    let values = args.list(0)?;
+
+    if values.is_empty() {
+        return eval_error(EmptyList, args.arg_pos(0));
+    }
+
+    let i = ctx.random().below(values.len());
+    Ok(values[i].clone())
+}
+
+// noise(x, y) or noise(x, y, z), between -1 and 1
+pub fn noise(args: &Args, ctx: &mut Context) -> EvalResult<Value> {
+    let z = if args.len() > 2 { args.plain(2)? } else { 0.0 };
+    let n = ctx.random().noise(args.plain(0)?, args.plain(1)?, z);
+    Ok(Value::from_number(n))
+}
+
+pub fn len(args: &Args) -> EvalResult<Value> {
+    match args.value(0) {
+        Value::List(values) => Ok(Value::from_number(values.len() as f64)),
+        Value::String(s) => Ok(Value::from_number(s.chars().count() as f64)),
+        v => eval_error(
+            TypeMismatch("list or string".to_owned(), v.name()),
+            args.arg_pos(0),
+        ),
+    }
+}
+
+// lists are values, so push returns a new list and leaves the original alone
+pub fn push(args: &Args) -> EvalResult<Value> {
+    let mut values = args.list(0)?;
+    values.push(args.value(1));
+    Ok(Value::List(values))
+}
+
+pub fn concat(args: &Args) -> EvalResult<Value> {
+    let mut values = vec![];
+    for i in 0..args.len() {
+        values.extend(args.list(i)?);
+    }
+    Ok(Value::List(values))
+}
+
+// range(end), range(start, end) or range(start, end, step), like `start..end step step`
+pub fn range(args: &Args) -> EvalResult<Value> {
+    let bound = |i: usize| -> EvalResult<RangeBound> { Ok((args.number(i)?, args.arg_pos(i))) };
+
+    match args.len() {
+        1 => range_list(
+            ((0.0, None), args.arg_pos(0)),
+            bound(0)?,
+            ((1.0, None), args.arg_pos(0)),
+        ),
+        2 => range_list(bound(0)?, bound(1)?, ((1.0, None), args.arg_pos(1))),
+        _ => range_list(bound(0)?, bound(1)?, bound(2)?),
+    }
+}
+
+// call the function given as arg `f` with a single value
+fn call_with(args: &Args, f: usize, value: Value, ctx: &mut Context) -> EvalResult<Value> {
+    let name = args.function(f)?;
+    let mut fn_args = Args::new();
+    fn_args.push(value, args.arg_pos(0));
+
+    call_function(&name, &fn_args, args.arg_pos(f), ctx)
+}
+
+pub fn map(args: &Args, ctx: &mut Context) -> EvalResult<Value> {
+    let values = args
+        .list(0)?
+        .into_iter()
+        .map(|value| call_with(args, 1, value, ctx))
+        .collect::<EvalResult<Vec<Value>>>()?;
+    Ok(Value::List(values))
+}
+
+pub fn filter(args: &Args, ctx: &mut Context) -> EvalResult<Value> {
+    let mut values = vec![];
+    for value in args.list(0)?.into_iter() {
+        let keep = call_with(args, 1, value.clone(), ctx)?;
+        if get_bool(keep, args.arg_pos(1))? {
+            values.push(value);
+        }
+    }
+    Ok(Value::List(values))
+}
+
+pub fn reverse(args: &Args) -> EvalResult<Value> {
+    let mut values = args.list(0)?;
+    values.reverse();
+    Ok(Value::List(values))
+}
+
+// sort(xs) or sort(xs, key) to sort by the result of calling key on each value
+pub fn sort(args: &Args, ctx: &mut Context) -> EvalResult<Value> {
+    let values = args.list(0)?;
+    let keys = if args.len() > 1 {
+        values
+            .iter()
+            .map(|value| call_with(args, 1, value.clone(), ctx))
+            .collect::<EvalResult<Vec<Value>>>()?
+    } else {
+        values.clone()
+    };
+
+    // sort_by can't fail, so the first comparison error is kept for later
+    let mut error = None;
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| {
+        let pos = args.arg_pos(0);
+        match compare_values(keys[a].clone(), pos, keys[b].clone(), pos) {
+            Ok(ordering) => ordering,
+            Err(e) => {
+                error.get_or_insert(e);
+                Ordering::Equal
+            }
+        }
+    });
+
+    match error {
+        Some(e) => Err(e),
+        None => Ok(Value::List(
+            order.into_iter().map(|i| values[i].clone()).collect(),
+        )),
+    }
+}
+
+// zip(xs, ys) pairs up values into two item lists, stopping at the shorter list
+pub fn zip(args: &Args) -> EvalResult<Value> {
+    let pairs = args
+        .list(0)?
+        .into_iter()
+        .zip(args.list(1)?)
+        .map(|(a, b)| Value::List(vec![a, b]))
+        .collect();
+    Ok(Value::List(pairs))
+}
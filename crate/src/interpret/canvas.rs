@@ -0,0 +1,182 @@
+use crate::lexer::Unit;
+
+use super::error::EvalErrorType::*;
+use super::*;
+
+// units the root svg can be sized in, the same as the absolute css units
+static UNITS: &[&str] = &["px", "mm", "cm", "in", "pt", "pc"];
+
+/// The size and background of the drawing, from a `canvas { ... }`
+/// declaration or the compile options. Missing values keep the defaults of a
+/// drawing that fills its container.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Canvas {
+    pub width: Option<f64>,
+    pub height: Option<f64>,
+    /// The min x, min y, width and height of the visible area.
+    pub view_box: Option<[f64; 4]>,
+    pub background: Option<String>,
+    /// The unit of `width` and `height`. The view box is always in user units.
+    pub units: Option<String>,
+}
+
+impl Canvas {
+    // values set in `other` replace the values of this canvas
+    fn merge(self, other: &Canvas) -> Canvas {
+        Canvas {
+            width: other.width.or(self.width),
+            height: other.height.or(self.height),
+            view_box: other.view_box.or(self.view_box),
+            background: other.background.clone().or(self.background),
+            units: other.units.clone().or(self.units),
+        }
+    }
+
+    pub fn is_valid_units(units: &str) -> bool {
+        UNITS.contains(&units)
+    }
+
+    /// The root `<svg>` element, with a background rect if there is one.
+    pub fn element(&self, range: Range) -> Element {
+        let size = |value: Option<f64>| match value {
+            Some(n) => format!("{}{}", n, self.units.as_ref().map_or("", |u| u.as_str())),
+            None => "100%".to_owned(),
+        };
+
+        let mut svg = Element::new("svg", range);
+        svg.set_attr("width", size(self.width));
+        svg.set_attr("height", size(self.height));
+        if let Some([x, y, width, height]) = self.view_box {
+            svg.set_attr("viewBox", format!("{} {} {} {}", x, y, width, height));
+        }
+        svg.set_attr("xmlns", "http://www.w3.org/2000/svg".to_owned());
+
+        if let Some(background) = &self.background {
+            // cover the whole view box, which might not start at the origin
+            let (x, y, width, height) = match self.view_box {
+                Some([x, y, width, height]) => (
+                    x.to_string(),
+                    y.to_string(),
+                    width.to_string(),
+                    height.to_string(),
+                ),
+                None => ("0".to_owned(), "0".to_owned(), size(None), size(None)),
+            };
+
+            // the background stays behind anything with a negative z
+            let mut rect = Element::new("rect", range);
+            rect.z = f64::NEG_INFINITY;
+            rect.set_attr("x", x);
+            rect.set_attr("y", y);
+            rect.set_attr("width", width);
+            rect.set_attr("height", height);
+            rect.set_attr("fill", background.clone());
+            svg.children.push(Node::Element(rect));
+        }
+
+        svg
+    }
+}
+
+// a width or height, which the compile options could also give as NaN or
+// infinity
+fn check_size(n: f64, pos: Pos) -> EvalResult<f64> {
+    if n.is_finite() && n >= 0.0 {
+        Ok(n)
+    } else {
+        eval_error(InvalidCanvasSize(n), pos)
+    }
+}
+
+// a width or height in user units
+fn get_size(value: Value, pos: Pos) -> EvalResult<f64> {
+    match get_number(value, pos)? {
+        (n, None) | (n, Some(Unit::Px)) => check_size(n, pos),
+        (_, unit) => eval_error(TypeMismatch(unit_name(None), unit_name(unit)), pos),
+    }
+}
+
+fn get_view_box(value: Value, pos: Pos) -> EvalResult<[f64; 4]> {
+    let values = match value {
+        Value::List(values) => values,
+        v => return eval_error(TypeMismatch("list".to_owned(), v.name()), pos),
+    };
+
+    if values.len() != 4 {
+        return eval_error(
+            TypeMismatch("list of 4 numbers".to_owned(), values.len().to_string()),
+            pos,
+        );
+    }
+
+    let mut view_box = [0.0; 4];
+    for (i, value) in values.into_iter().enumerate() {
+        view_box[i] = match get_number(value, pos)? {
+            (n, None) | (n, Some(Unit::Px)) => n,
+            (_, unit) => return eval_error(TypeMismatch(unit_name(None), unit_name(unit)), pos),
+        };
+    }
+
+    Ok(view_box)
+}
+
+/// The canvas declared by the program, with the compile options applied on top.
+/// Without a view box, one is made from the program's own width and height so
+/// overriding the size scales the drawing instead of cropping it.
+pub fn find_canvas(program: &Program, options: &Options, ctx: &mut Context) -> EvalResult<Canvas> {
+    let mut canvas: Option<Canvas> = None;
+
+    for decl in program.decls.iter() {
+        if let Decl::CanvasDecl(expr, pos) = decl {
+            if canvas.is_some() {
+                return eval_error(CanvasAlreadyDefined, *pos);
+            }
+
+            let mut declared = Canvas::default();
+            let fields = get_record(eval_expression(expr, ctx)?, expr.pos())?;
+            for (key, value) in fields.into_iter() {
+                match key.as_str() {
+                    "width" => declared.width = Some(get_size(value, expr.pos())?),
+                    "height" => declared.height = Some(get_size(value, expr.pos())?),
+                    "viewBox" => declared.view_box = Some(get_view_box(value, expr.pos())?),
+                    "background" => {
+                        declared.background = Some(match value {
+                            Value::Color(c) => c.to_string(),
+                            v => get_string(v, expr.pos())?,
+                        })
+                    }
+                    "units" => {
+                        let units = get_string(value, expr.pos())?;
+                        if !Canvas::is_valid_units(&units) {
+                            return eval_error(InvalidCanvasUnits(units), expr.pos());
+                        }
+                        declared.units = Some(units);
+                    }
+                    _ => return eval_error(UnExpectedArg("canvas".to_owned(), key), expr.pos()),
+                }
+            }
+
+            canvas = Some(declared);
+        }
+    }
+
+    for size in [options.canvas.width, options.canvas.height]
+        .iter()
+        .flatten()
+    {
+        check_size(*size, create_pos(0, 0))?;
+    }
+
+    if let Some(units) = &options.canvas.units {
+        if !Canvas::is_valid_units(units) {
+            return eval_error(InvalidCanvasUnits(units.clone()), create_pos(0, 0));
+        }
+    }
+
+    let mut canvas = canvas.unwrap_or_default();
+    if let (None, Some(width), Some(height)) = (canvas.view_box, canvas.width, canvas.height) {
+        canvas.view_box = Some([0.0, 0.0, width, height]);
+    }
+
+    Ok(canvas.merge(&options.canvas))
+}
@@ -6,14 +6,40 @@ use crate::utils::*;
 #[derive(Debug, PartialEq, Clone)]
 pub enum EvalErrorType {
     VariableNotDefined(String),
+    FieldNotDefined(String),
     ShapeNotDefined(String),
+    FunctionNotDefined(String),
     TypeMismatch(String, String),
     SvgExpectsString(String),
+    ModuloByZero,
+    NonFiniteResult,
+    RangeStepZero,
+    RangeTooLarge(usize),
+    EmptyRange,
+    EmptyList,
+    InvalidIndex(f64),
+    IndexOutOfBounds(f64, usize),
+    SeedAlreadyDefined,
+    CanvasAlreadyDefined,
+    InvalidCanvasSize(f64),
+    InvalidCanvasUnits(String),
     ShapeAlreadyDefined(String),
+    FunctionAlreadyDefined(String),
+    LayerAlreadyDefined(String),
+    FunctionUsedAsShape(String),
+    ConstAlreadyDefined(String),
+    CyclicConst(String),
+    ConstUsedBeforeDefinition(String),
     NumArgs(String, usize, usize),
     InvalidArgName(String, String),
     MissingArgs(String, Vec<String>),
     UnExpectedArg(String, String),
+    UnExpectedChildren(String),
+    MissingChildren(String),
+    UnknownElement(String),
+    UnknownAttribute(String, String),
+    UnsafeAttrValue(String),
+    RawSvgDisabled,
     MissingRequiredArg(String, String),
     StackOverflow(Vec<String>),
     MissingMain,
@@ -24,7 +50,11 @@ impl fmt::Display for EvalErrorType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             EvalErrorType::VariableNotDefined(name) => write!(f, "Variable `{}` not defined", name),
+            EvalErrorType::FieldNotDefined(name) => write!(f, "Record has no field `{}`", name),
             EvalErrorType::ShapeNotDefined(name) => write!(f, "Shape `{}` not defined", name),
+            EvalErrorType::FunctionNotDefined(name) => {
+                write!(f, "Function `{}` not defined", name)
+            }
             EvalErrorType::TypeMismatch(expected, received) => write!(
                 f,
                 "Typemismatch. Expected: {}, Received: {}",
@@ -32,10 +62,58 @@ impl fmt::Display for EvalErrorType {
             ),
             EvalErrorType::SvgExpectsString(received) => write!(
                 f,
-                "svg value arg needs to be a string. Received {}",
+                "raw_svg value arg needs to be a string. Received {}",
                 received
             ),
+            EvalErrorType::ModuloByZero => write!(f, "Modulo by zero"),
+            EvalErrorType::NonFiniteResult => {
+                write!(f, "Result is not a finite number (e.g. division by zero)")
+            }
+            EvalErrorType::RangeStepZero => write!(f, "Range step cannot be zero"),
+            EvalErrorType::RangeTooLarge(max) => {
+                write!(f, "Range has more than {} items", max)
+            }
+            EvalErrorType::EmptyRange => write!(f, "Range is empty"),
+            EvalErrorType::EmptyList => write!(f, "List is empty"),
+            EvalErrorType::InvalidIndex(index) => {
+                write!(f, "List index {} is not a whole number", index)
+            }
+            EvalErrorType::IndexOutOfBounds(index, len) => write!(
+                f,
+                "List index {} is out of bounds for a list of length {}",
+                index, len
+            ),
+            EvalErrorType::SeedAlreadyDefined => write!(f, "Seed already defined"),
+            EvalErrorType::CanvasAlreadyDefined => write!(f, "Canvas already defined"),
+            EvalErrorType::InvalidCanvasSize(n) => {
+                write!(
+                    f,
+                    "Canvas size must be a finite number of at least 0. Received {}",
+                    n
+                )
+            }
+            EvalErrorType::InvalidCanvasUnits(units) => write!(
+                f,
+                "Canvas units must be one of px, mm, cm, in, pt or pc. Received {}",
+                units
+            ),
             EvalErrorType::ShapeAlreadyDefined(name) => write!(f, "Shape {} already defined", name),
+            EvalErrorType::LayerAlreadyDefined(name) => {
+                write!(f, "Layer {} already defined", name)
+            }
+            EvalErrorType::FunctionAlreadyDefined(name) => {
+                write!(f, "Function {} already defined", name)
+            }
+            EvalErrorType::FunctionUsedAsShape(name) => write!(
+                f,
+                "{} is a function, it can only be called from an expression",
+                name
+            ),
+            EvalErrorType::ConstAlreadyDefined(name) => write!(f, "Const {} already defined", name),
+            EvalErrorType::CyclicConst(name) => write!(f, "Const {} depends on itself", name),
+            EvalErrorType::ConstUsedBeforeDefinition(name) => {
+                write!(f, "Const {} used before it is defined", name)
+            }
             EvalErrorType::NumArgs(func, expected, received) => write!(
                 f,
                 "Incorrect number of args to {}. Expected: {}, Received: {}",
@@ -50,6 +128,23 @@ impl fmt::Display for EvalErrorType {
             EvalErrorType::UnExpectedArg(func, arg) => {
                 write!(f, "Unexpected arg {} to {}", arg, func)
             }
+            EvalErrorType::UnExpectedChildren(name) => {
+                write!(f, "{} does not take a block of children", name)
+            }
+            EvalErrorType::MissingChildren(name) => {
+                write!(f, "{} needs a block of children to apply to", name)
+            }
+            EvalErrorType::UnknownElement(name) => write!(f, "`{}` is not an SVG element", name),
+            EvalErrorType::UnknownAttribute(element, name) => {
+                write!(f, "`{}` is not an SVG attribute of {}", name, element)
+            }
+            EvalErrorType::UnsafeAttrValue(name) => {
+                write!(f, "Value of attribute {} could run a script", name)
+            }
+            EvalErrorType::RawSvgDisabled => write!(
+                f,
+                "raw_svg is disabled. Enable it in the compile options to write raw markup"
+            ),
             EvalErrorType::MissingRequiredArg(func, arg) => {
                 write!(f, "Missing required arg {} to {}", arg, func)
             }
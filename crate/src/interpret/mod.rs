@@ -1,30 +1,74 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::f64::consts::PI;
 use std::fmt;
 
+use crate::color::Color;
 use crate::lexer;
+use crate::lexer::Unit;
 use crate::parser;
 use crate::parser::ast::*;
 use crate::utils::*;
 
+mod builtins;
+mod canvas;
 mod error;
+mod random;
 mod stdlib;
+mod svg;
+mod transform;
 
+use builtins::Args;
+pub use canvas::Canvas;
 use error::EvalErrorType::*;
 use error::*;
+use random::Random;
+pub use svg::{Element, Node};
 
 static STACK_LIMIT: usize = 256;
+static RANGE_LIMIT: usize = 100_000;
+static DEFAULT_SEED: u64 = 0;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Value {
-    Number(f64),
+    Number(f64, Option<Unit>),
+    Color(Color),
     String(String),
+    Bool(bool),
+    // a 2D vector or point, with both components in the same unit
+    Vec2(f64, f64, Option<Unit>),
+    List(Vec<Value>),
+    // fields are kept in the order they were first set
+    Record(Vec<(String, Value)>),
+    Function(Ident),
 }
 
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Value::Number(n) => write!(f, "{}", n),
+            Value::Number(n, None) => write!(f, "{}", n),
+            Value::Number(n, Some(unit)) => write!(f, "{}{}", n, unit),
+            Value::Color(c) => write!(f, "{}", c),
+            Value::Bool(b) => write!(f, "{}", b),
             Value::String(s) => write!(f, "{}", s),
+            Value::Vec2(x, y, unit) => write!(
+                f,
+                "{},{}",
+                Value::Number(*x, *unit),
+                Value::Number(*y, *unit)
+            ),
+            Value::List(values) => {
+                let values: Vec<String> = values.iter().map(|v| format!("{}", v)).collect();
+                write!(f, "[{}]", values.join(", "))
+            }
+            Value::Record(fields) => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key, value))
+                    .collect();
+                write!(f, "{{{}}}", fields.join(", "))
+            }
+            Value::Function(name) => write!(f, "fn {}", name),
         }
     }
 }
@@ -36,8 +80,14 @@ trait Name {
 impl Name for Value {
     fn name(&self) -> String {
         match self {
-            Value::Number(_) => "number".to_owned(),
+            Value::Number(_, unit) => unit_name(*unit),
+            Value::Color(_) => "color".to_owned(),
+            Value::Bool(_) => "bool".to_owned(),
             Value::String(_) => "string".to_owned(),
+            Value::Vec2(_, _, _) => "vector".to_owned(),
+            Value::List(_) => "list".to_owned(),
+            Value::Record(_) => "record".to_owned(),
+            Value::Function(_) => "function".to_owned(),
         }
     }
 }
@@ -49,11 +99,14 @@ struct Context<'a> {
     parent: Option<&'a Context<'a>>,
     scope: HashMap<String, Value>,
     shapes: HashMap<String, Shape>,
+    functions: HashMap<String, Function>,
+    random: Option<Random>,
+    raw_svg: bool,
 }
 
 impl Value {
     pub fn from_number(n: f64) -> Value {
-        Value::Number(n)
+        Value::Number(n, None)
     }
 
     pub fn from_string(s: &str) -> Value {
@@ -74,6 +127,9 @@ impl<'a> Context<'a> {
             parent: None,
             scope: HashMap::new(),
             shapes: HashMap::new(),
+            functions: HashMap::new(),
+            random: Some(Random::new(DEFAULT_SEED)),
+            raw_svg: false,
         }
     }
 
@@ -83,11 +139,31 @@ impl<'a> Context<'a> {
             parent: Some(ctx),
             scope: HashMap::new(),
             shapes: HashMap::new(),
+            functions: HashMap::new(),
+            random: None,
+            raw_svg: false,
+        }
+    }
+
+    // shapes, functions, the random state and options are only stored in the
+    // root context
+    pub fn root(&self) -> &Context<'a> {
+        match self.parent {
+            Some(p) => p.root(),
+            None => self,
         }
     }
 
-    pub fn set_scope(&mut self, scope: HashMap<String, Value>) {
-        self.scope = scope;
+    pub fn get_shape(&self, name: &str) -> Option<&Shape> {
+        self.root().shapes.get(name)
+    }
+
+    pub fn get_function(&self, name: &str) -> Option<&Function> {
+        self.root().functions.get(name)
+    }
+
+    pub fn random(&self) -> &Random {
+        self.root().random.as_ref().unwrap()
     }
 
     pub fn get(&self, key: &str) -> Option<Value> {
@@ -107,14 +183,16 @@ impl<'a> Context<'a> {
 
 fn eval_literal(lit: &Literal) -> EvalResult<Value> {
     match lit {
-        Literal::Number(n) => Ok(Value::Number(*n)),
+        Literal::Number(n, unit) => Ok(Value::Number(*n, *unit)),
+        Literal::Color(c) => Ok(Value::Color(*c)),
+        Literal::Bool(b) => Ok(Value::Bool(*b)),
         Literal::String(s) => Ok(Value::from_string(s)),
     }
 }
 
-fn get_number(value: Value, pos: Pos) -> EvalResult<f64> {
+fn get_number(value: Value, pos: Pos) -> EvalResult<(f64, Option<Unit>)> {
     match value {
-        Value::Number(n) => Ok(n),
+        Value::Number(n, unit) => Ok((n, unit)),
         v => return eval_error(TypeMismatch("number".to_owned(), v.name()), pos),
     }
 }
@@ -126,16 +204,16 @@ fn get_string(value: Value, pos: Pos) -> EvalResult<String> {
     }
 }
 
-fn is_string(value: &Value) -> bool {
+fn get_bool(value: Value, pos: Pos) -> EvalResult<bool> {
     match value {
-        Value::String(_) => true,
-        _ => false,
+        Value::Bool(b) => Ok(b),
+        v => eval_error(TypeMismatch("bool".to_owned(), v.name()), pos),
     }
 }
 
-fn is_number(value: &Value) -> bool {
+fn is_string(value: &Value) -> bool {
     match value {
-        Value::Number(_) => true,
+        Value::String(_) => true,
         _ => false,
     }
 }
@@ -146,292 +224,2965 @@ fn eval_binary(
     rhs_expr: &Expr,
     ctx: &mut Context,
 ) -> EvalResult<Value> {
+    // the right hand side of a logical operator is only evaluated when needed
+    if op == BinOp::And || op == BinOp::Or {
+        let lhs = get_bool(eval_expression(lhs_expr, ctx)?, lhs_expr.pos())?;
+        if lhs == (op == BinOp::Or) {
+            return Ok(Value::Bool(lhs));
+        }
+
+        let rhs = get_bool(eval_expression(rhs_expr, ctx)?, rhs_expr.pos())?;
+        return Ok(Value::Bool(rhs));
+    }
+
     let lhs = eval_expression(lhs_expr, ctx)?;
     let rhs = eval_expression(rhs_expr, ctx)?;
 
+    binary_values(op, lhs, lhs_expr.pos(), rhs, rhs_expr.pos())
+}
+
+fn is_vec(value: &Value) -> bool {
+    matches!(value, Value::Vec2(_, _, _))
+}
+
+fn binary_values(
+    op: BinOp,
+    lhs: Value,
+    lhs_pos: Pos,
+    rhs: Value,
+    rhs_pos: Pos,
+) -> EvalResult<Value> {
+    let arithmetic = matches!(op, BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div);
+    if arithmetic && (is_vec(&lhs) || is_vec(&rhs)) && !is_string(&lhs) && !is_string(&rhs) {
+        return vec_binary(op, lhs, lhs_pos, rhs, rhs_pos);
+    }
+
     match op {
         BinOp::Mul => {
-            let lhs = get_number(lhs, lhs_expr.pos())?;
-            let rhs = get_number(rhs, rhs_expr.pos())?;
-            Ok(Value::Number(lhs * rhs))
+            let (lhs, lhs_unit) = get_number(lhs, lhs_pos)?;
+            let (rhs, rhs_unit) = get_number(rhs, rhs_pos)?;
+
+            match (lhs_unit, rhs_unit) {
+                (Some(_), Some(_)) => {
+                    eval_error(TypeMismatch(unit_name(None), unit_name(rhs_unit)), rhs_pos)
+                }
+                _ => Ok(Value::Number(lhs * rhs, lhs_unit.or(rhs_unit))),
+            }
         }
         BinOp::Div => {
-            let lhs = get_number(lhs, lhs_expr.pos())?;
-            let rhs = get_number(rhs, rhs_expr.pos())?;
-            Ok(Value::Number(lhs / rhs))
+            let lhs = get_number(lhs, lhs_pos)?;
+            let rhs = get_number(rhs, rhs_pos)?;
+
+            match (lhs.1, rhs.1) {
+                (unit, None) => Ok(Value::Number(lhs.0 / rhs.0, unit)),
+                (None, Some(_)) => {
+                    eval_error(TypeMismatch(unit_name(None), unit_name(rhs.1)), rhs_pos)
+                }
+                // dividing two quantities of the same kind gives a plain ratio
+                (Some(_), Some(_)) => {
+                    let (lhs, rhs, _) = unify_units(lhs, rhs, rhs_pos)?;
+                    Ok(Value::from_number(lhs / rhs))
+                }
+            }
+        }
+        BinOp::IntDiv => {
+            let (lhs, lhs_unit) = get_number(lhs, lhs_pos)?;
+            let (rhs, rhs_unit) = get_number(rhs, rhs_pos)?;
+
+            match (lhs_unit, rhs_unit) {
+                (unit, None) => Ok(Value::Number((lhs / rhs).floor(), unit)),
+                (None, Some(_)) => {
+                    eval_error(TypeMismatch(unit_name(None), unit_name(rhs_unit)), rhs_pos)
+                }
+                (Some(_), Some(_)) => {
+                    let (lhs, rhs, _) = unify_units((lhs, lhs_unit), (rhs, rhs_unit), rhs_pos)?;
+                    Ok(Value::from_number((lhs / rhs).floor()))
+                }
+            }
+        }
+        // the result takes the sign of the divisor so values wrap around
+        BinOp::Mod => {
+            let lhs = get_number(lhs, lhs_pos)?;
+            let rhs = get_number(rhs, rhs_pos)?;
+            let (lhs, rhs, unit) = unify_units(lhs, rhs, rhs_pos)?;
+
+            if rhs == 0.0 {
+                return eval_error(ModuloByZero, rhs_pos);
+            }
+
+            Ok(Value::Number(lhs - rhs * (lhs / rhs).floor(), unit))
+        }
+        BinOp::Pow => {
+            let lhs = get_number(lhs, lhs_pos)?;
+            let rhs = get_number(rhs, rhs_pos)?;
+
+            match (lhs, rhs) {
+                ((lhs, None), (rhs, None)) => Ok(Value::from_number(lhs.powf(rhs))),
+                ((_, None), (_, unit)) => {
+                    eval_error(TypeMismatch(unit_name(None), unit_name(unit)), rhs_pos)
+                }
+                ((_, unit), _) => {
+                    eval_error(TypeMismatch(unit_name(None), unit_name(unit)), lhs_pos)
+                }
+            }
         }
         BinOp::Add => {
-            if !is_number(&lhs) || !is_number(&rhs) {
+            if is_string(&lhs) || is_string(&rhs) {
                 Ok(Value::String(format!("{}{}", lhs, rhs)))
             } else {
-                let lhs = get_number(lhs, lhs_expr.pos())?;
-                let rhs = get_number(rhs, rhs_expr.pos())?;
+                let lhs = get_number(lhs, lhs_pos)?;
+                let rhs = get_number(rhs, rhs_pos)?;
+                let (lhs, rhs, unit) = unify_units(lhs, rhs, rhs_pos)?;
 
-                Ok(Value::Number(lhs + rhs))
+                Ok(Value::Number(lhs + rhs, unit))
             }
         }
         BinOp::Sub => {
-            let lhs = get_number(lhs, lhs_expr.pos())?;
-            let rhs = get_number(rhs, rhs_expr.pos())?;
-            Ok(Value::Number(lhs - rhs))
+            let lhs = get_number(lhs, lhs_pos)?;
+            let rhs = get_number(rhs, rhs_pos)?;
+            let (lhs, rhs, unit) = unify_units(lhs, rhs, rhs_pos)?;
+
+            Ok(Value::Number(lhs - rhs, unit))
+        }
+        BinOp::Eq => Ok(Value::Bool(values_equal(lhs, rhs, rhs_pos)?)),
+        BinOp::NotEq => Ok(Value::Bool(!values_equal(lhs, rhs, rhs_pos)?)),
+        BinOp::Lt | BinOp::LtEq | BinOp::Gt | BinOp::GtEq => {
+            let ordering = compare_values(lhs, lhs_pos, rhs, rhs_pos)?;
+            let result = match op {
+                BinOp::Lt => ordering == Ordering::Less,
+                BinOp::LtEq => ordering != Ordering::Greater,
+                BinOp::Gt => ordering == Ordering::Greater,
+                _ => ordering != Ordering::Less,
+            };
+
+            Ok(Value::Bool(result))
         }
+        BinOp::And | BinOp::Or => unreachable!(),
     }
 }
 
-fn eval_unary(op: UnOp, expr: &Expr, ctx: &mut Context) -> EvalResult<Value> {
-    let value = eval_expression(expr, ctx)?;
-    let value = get_number(value, expr.pos())?;
-
-    match op {
-        UnOp::Neg => Ok(Value::from_number(value * -1.0)),
+// Vectors add and subtract componentwise and can be scaled by a number. Each
+// component follows the same unit rules as plain numbers.
+fn vec_binary(op: BinOp, lhs: Value, lhs_pos: Pos, rhs: Value, rhs_pos: Pos) -> EvalResult<Value> {
+    let component = |lhs: Value, rhs: Value| binary_values(op.clone(), lhs, lhs_pos, rhs, rhs_pos);
+
+    match (&op, lhs, rhs) {
+        (BinOp::Add, Value::Vec2(x1, y1, u1), Value::Vec2(x2, y2, u2))
+        | (BinOp::Sub, Value::Vec2(x1, y1, u1), Value::Vec2(x2, y2, u2)) => make_vec(
+            component(Value::Number(x1, u1), Value::Number(x2, u2))?,
+            component(Value::Number(y1, u1), Value::Number(y2, u2))?,
+            rhs_pos,
+        ),
+        (BinOp::Mul, Value::Vec2(x, y, unit), rhs @ Value::Number(_, _))
+        | (BinOp::Div, Value::Vec2(x, y, unit), rhs @ Value::Number(_, _)) => make_vec(
+            component(Value::Number(x, unit), rhs.clone())?,
+            component(Value::Number(y, unit), rhs)?,
+            rhs_pos,
+        ),
+        (BinOp::Mul, lhs @ Value::Number(_, _), Value::Vec2(x, y, unit)) => make_vec(
+            component(lhs.clone(), Value::Number(x, unit))?,
+            component(lhs, Value::Number(y, unit))?,
+            rhs_pos,
+        ),
+        (BinOp::Add, Value::Vec2(_, _, _), rhs) | (BinOp::Sub, Value::Vec2(_, _, _), rhs) => {
+            eval_error(TypeMismatch("vector".to_owned(), rhs.name()), rhs_pos)
+        }
+        (_, Value::Vec2(_, _, _), rhs) => {
+            eval_error(TypeMismatch("number".to_owned(), rhs.name()), rhs_pos)
+        }
+        (_, lhs, _) => eval_error(TypeMismatch("vector".to_owned(), lhs.name()), lhs_pos),
     }
 }
 
-fn eval_expression(expr: &Expr, ctx: &mut Context) -> EvalResult<Value> {
-    match expr {
-        Expr::Name(n, r) => match ctx.get(n) {
-            Some(value) => Ok(value),
-            None => return eval_error(VariableNotDefined(n.clone()), r.start),
-        },
-        Expr::Literal(lit, _) => eval_literal(lit),
-        Expr::Binary(ref lhs, op, ref rhs, _) => eval_binary(op.clone(), lhs, rhs, ctx),
-        Expr::Unary(op, ref expr, _) => eval_unary(op.clone(), expr, ctx),
-        Expr::Grouping(ref expr) => eval_expression(expr, ctx),
-    }
-}
+// Both components of a vector share a unit.
+fn make_vec(x: Value, y: Value, pos: Pos) -> EvalResult<Value> {
+    let x = get_number(x, pos)?;
+    let y = get_number(y, pos)?;
+    let (x, y, unit) = unify_units(x, y, pos)?;
 
-fn eval_svg_call(call: &FunCall, ctx: &mut Context) -> EvalResult<Value> {
-    if call.args.len() != 1 {
-        return eval_error(NumArgs(call.ident.clone(), 1, call.args.len()), call.pos());
-    }
+    Ok(Value::Vec2(x, y, unit))
+}
 
-    let arg = &call.args[0];
-    if arg.name != "value" {
-        return eval_error(
-            MissingArgs(call.ident.clone(), vec!["value".to_owned()]),
-            call.pos(),
-        );
+// Operations like `1 / 0` produce infinities and NaN which are meaningless in
+// an SVG, so they are reported at the operator that produced them.
+fn check_finite(value: Value, pos: Pos) -> EvalResult<Value> {
+    match value {
+        Value::Number(n, _) if !n.is_finite() => eval_error(NonFiniteResult, pos),
+        Value::Vec2(x, y, _) if !x.is_finite() || !y.is_finite() => {
+            eval_error(NonFiniteResult, pos)
+        }
+        value => Ok(value),
     }
+}
 
-    let value = eval_expression(&arg.expr, ctx)?;
+fn values_equal(lhs: Value, rhs: Value, pos: Pos) -> EvalResult<bool> {
+    match (lhs, rhs) {
+        (Value::Vec2(x1, y1, u1), Value::Vec2(x2, y2, u2)) => {
+            let (x1, x2, _) = unify_units((x1, u1), (x2, u2), pos)?;
+            let (y1, y2, _) = unify_units((y1, u1), (y2, u2), pos)?;
+            Ok(x1 == x2 && y1 == y2)
+        }
+        // records with the same fields are equal no matter the field order
+        (Value::Record(a), Value::Record(b)) => {
+            if a.len() != b.len() {
+                return Ok(false);
+            }
 
-    let value = match get_string(value, arg.expr.pos()) {
-        Err(e) => match &e.error_type {
-            TypeMismatch(_, received) => {
-                return eval_error(SvgExpectsString(received.clone()), e.pos())
+            for (key, value) in a.into_iter() {
+                let equal = match get_field(&b, &key) {
+                    Some(other) => values_equal(value, other, pos)?,
+                    None => false,
+                };
+                if !equal {
+                    return Ok(false);
+                }
             }
-            error_type => return eval_error(error_type.clone(), e.pos()),
-        },
-        Ok(v) => v,
-    };
 
-    Ok(Value::String(value))
+            Ok(true)
+        }
+        (Value::Number(a, a_unit), Value::Number(b, b_unit)) => {
+            let (a, b, _) = unify_units((a, a_unit), (b, b_unit), pos)?;
+            Ok(a == b)
+        }
+        (lhs, rhs) => Ok(lhs == rhs),
+    }
 }
 
-fn eval_call(call: &FunCall, ctx: &mut Context) -> EvalResult<Value> {
-    ctx.stack.push(call.ident.clone());
-    if ctx.stack.len() > STACK_LIMIT {
-        return eval_error(StackOverflow(ctx.stack.clone()), call.pos());
+// numbers and strings can be ordered
+fn compare_values(lhs: Value, lhs_pos: Pos, rhs: Value, rhs_pos: Pos) -> EvalResult<Ordering> {
+    match (lhs, rhs) {
+        (Value::Number(a, a_unit), Value::Number(b, b_unit)) => {
+            let (a, b, _) = unify_units((a, a_unit), (b, b_unit), rhs_pos)?;
+            Ok(a.partial_cmp(&b).unwrap_or(Ordering::Equal))
+        }
+        (Value::String(a), Value::String(b)) => Ok(a.cmp(&b)),
+        (Value::Number(_, unit), rhs) => {
+            eval_error(TypeMismatch(unit_name(unit), rhs.name()), rhs_pos)
+        }
+        (Value::String(_), rhs) => {
+            eval_error(TypeMismatch("string".to_owned(), rhs.name()), rhs_pos)
+        }
+        (lhs, _) => eval_error(
+            TypeMismatch("number or string".to_owned(), lhs.name()),
+            lhs_pos,
+        ),
     }
+}
 
-    if call.ident == "svg" {
-        return eval_svg_call(call, ctx);
+fn get_field(fields: &[(String, Value)], key: &str) -> Option<Value> {
+    fields
+        .iter()
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value.clone())
+}
+
+// later fields replace earlier ones with the same name but keep their position
+fn set_field(fields: &mut Vec<(String, Value)>, key: &str, value: Value) {
+    match fields.iter_mut().find(|(name, _)| name == key) {
+        Some(field) => field.1 = value,
+        None => fields.push((key.to_owned(), value)),
     }
+}
 
-    let shape = match ctx.shapes.get(&call.ident) {
-        Some(shape) => shape.clone(),
-        None => return eval_error(ShapeNotDefined(call.ident.clone()), call.pos()),
-    };
+fn get_record(value: Value, pos: Pos) -> EvalResult<Vec<(String, Value)>> {
+    match value {
+        Value::Record(fields) => Ok(fields),
+        v => eval_error(TypeMismatch("record".to_owned(), v.name()), pos),
+    }
+}
 
-    let mut args: HashMap<String, Value> = HashMap::new();
+fn unit_name(unit: Option<Unit>) -> String {
+    match unit {
+        None => "number".to_owned(),
+        Some(unit) => format!("number in {}", unit),
+    }
+}
 
-    for shape_arg in shape.args.iter() {
-        let value = match call.args.iter().find(|arg| arg.name == shape_arg.name) {
-            None => {
-                // use default value if it exists
-                match &shape_arg.default {
-                    None => {
-                        return eval_error(
-                            MissingRequiredArg(call.ident.clone(), shape_arg.name.clone()),
-                            call.pos(),
-                        )
-                    }
-                    Some(default_expr) => eval_expression(default_expr, ctx)?,
-                }
-            }
-            Some(call_arg) => eval_expression(&call_arg.expr, ctx)?,
-        };
+fn to_radians(n: f64, unit: Unit) -> f64 {
+    match unit {
+        Unit::Deg => n.to_radians(),
+        Unit::Turn => n * 2.0 * PI,
+        _ => n,
+    }
+}
 
-        args.insert(shape_arg.name.clone(), value);
+fn from_radians(n: f64, unit: Unit) -> f64 {
+    match unit {
+        Unit::Deg => n.to_degrees(),
+        Unit::Turn => n / (2.0 * PI),
+        _ => n,
     }
+}
 
-    let current_scope = ctx.scope.clone();
+// Bring two numbers into the same unit so they can be added or subtracted.
+// Angles are converted to the unit of the left hand side and unitless
+// numbers take on the unit of the other number.
+fn unify_units(
+    lhs: (f64, Option<Unit>),
+    rhs: (f64, Option<Unit>),
+    pos: Pos,
+) -> EvalResult<(f64, f64, Option<Unit>)> {
+    match (lhs.1, rhs.1) {
+        (None, unit) | (unit, None) => Ok((lhs.0, rhs.0, unit)),
+        (Some(a), Some(b)) if a == b => Ok((lhs.0, rhs.0, Some(a))),
+        (Some(a), Some(b)) if a.is_angle() && b.is_angle() => {
+            Ok((lhs.0, from_radians(to_radians(rhs.0, b), a), Some(a)))
+        }
+        (a, b) => eval_error(TypeMismatch(unit_name(a), unit_name(b)), pos),
+    }
+}
 
-    ctx.set_scope(args);
-    let result = eval_block(&shape.block, ctx)?;
-    ctx.set_scope(current_scope);
+fn eval_unary(op: UnOp, expr: &Expr, ctx: &mut Context) -> EvalResult<Value> {
+    let value = eval_expression(expr, ctx)?;
 
-    Ok(result)
+    match op {
+        UnOp::Neg => match value {
+            Value::Vec2(x, y, unit) => Ok(Value::Vec2(-x, -y, unit)),
+            value => {
+                let (value, unit) = get_number(value, expr.pos())?;
+                Ok(Value::Number(-value, unit))
+            }
+        },
+        UnOp::Not => Ok(Value::Bool(!get_bool(value, expr.pos())?)),
+    }
 }
 
-fn eval_block(block: &Block, ctx: &mut Context) -> EvalResult<Value> {
-    let mut out: String = "".to_owned();
-
-    for call in block.calls.iter() {
-        match eval_call(call, ctx)? {
-            Value::String(s) => out.push_str(s.as_str()),
-            _ => panic!("call should return string value."),
+// Each kind of expression is evaluated in its own function to keep the stack
+// frames of deeply nested or recursive calls small.
+fn eval_expression(expr: &Expr, ctx: &mut Context) -> EvalResult<Value> {
+    match expr {
+        Expr::Name(n, r) => eval_name(n, *r, ctx),
+        Expr::Literal(lit, _) => eval_literal(lit),
+        Expr::Binary(ref lhs, op, ref rhs, pos) => {
+            let value = eval_binary(op.clone(), lhs, rhs, ctx)?;
+            check_finite(value, *pos)
+        }
+        Expr::Unary(op, ref expr, _) => eval_unary(op.clone(), expr, ctx),
+        Expr::Grouping(ref expr) => eval_expression(expr, ctx),
+        Expr::Call(name, ref arg_exprs, range) => eval_call_expr(name, arg_exprs, *range, ctx),
+        Expr::List(ref exprs, _) => {
+            let values = exprs
+                .iter()
+                .map(|expr| eval_expression(expr, ctx))
+                .collect::<EvalResult<Vec<Value>>>()?;
+            Ok(Value::List(values))
+        }
+        Expr::Tuple(ref x, ref y, _) => {
+            let x_value = eval_expression(x, ctx)?;
+            let y_value = eval_expression(y, ctx)?;
+            get_number(x_value.clone(), x.pos())?;
+            make_vec(x_value, y_value, y.pos())
+        }
+        Expr::Record(ref entries, _) => eval_record(entries, ctx),
+        Expr::Field(ref record, name, pos) => eval_field(record, name, *pos, ctx),
+        Expr::Index(ref list, ref index, _) => eval_index(list, index, ctx),
+        Expr::Interpolated(ref parts, _) => eval_interpolated(parts, ctx),
+        Expr::Range(ref start, ref end, ref step, _) => {
+            eval_range(start, end, step.as_deref(), ctx)
+        }
+        Expr::Conditional(ref cond, ref then_expr, ref else_expr, _) => {
+            if get_bool(eval_expression(cond, ctx)?, cond.pos())? {
+                eval_expression(then_expr, ctx)
+            } else {
+                eval_expression(else_expr, ctx)
+            }
         }
     }
+}
+
+fn eval_name(name: &str, range: Range, ctx: &mut Context) -> EvalResult<Value> {
+    if let Some(value) = ctx.get(name) {
+        return Ok(value);
+    }
 
-    Ok(Value::String(out))
+    match NATIVE_CONSTS.iter().find(|(n, _)| *n == name) {
+        Some((_, value)) => Ok(Value::from_number(*value)),
+        // functions can be passed around by name, as in `map(xs, double)`
+        None if ctx.get_function(name).is_some() || is_native_fn(name) => {
+            Ok(Value::Function(name.to_owned()))
+        }
+        None => eval_error(VariableNotDefined(name.to_owned()), range.start),
+    }
 }
 
-fn find_shapes(
-    shapes: HashMap<String, Shape>,
-    program: &Program,
-    error_missing_main: bool,
-) -> EvalResult<HashMap<String, Shape>> {
-    let mut shapes: HashMap<String, Shape> = shapes.clone();
-    let mut found_main = false;
+fn eval_call_expr(
+    name: &str,
+    arg_exprs: &[Expr],
+    range: Range,
+    ctx: &mut Context,
+) -> EvalResult<Value> {
+    let mut args = Args::new();
+    for expr in arg_exprs.iter() {
+        let value = eval_expression(expr, ctx)?;
+        args.push(value, expr.pos());
+    }
 
-    for decl in program.decls.iter() {
-        match &decl {
-            Decl::ShapeDecl(shape) => {
-                if shapes.contains_key(&shape.name) {
-                    return eval_error(ShapeAlreadyDefined(shape.name.clone()), shape.pos());
-                }
+    call_function(name, &args, range.start, ctx)
+}
 
-                if shape.name == "main" {
-                    found_main = true;
+fn eval_record(entries: &[RecordEntry], ctx: &mut Context) -> EvalResult<Value> {
+    let mut fields = vec![];
+    for entry in entries.iter() {
+        match entry {
+            RecordEntry::Field(key, expr) => {
+                let value = eval_expression(expr, ctx)?;
+                set_field(&mut fields, key, value);
+            }
+            RecordEntry::Spread(expr) => {
+                let value = eval_expression(expr, ctx)?;
+                for (key, value) in get_record(value, expr.pos())?.into_iter() {
+                    set_field(&mut fields, &key, value);
                 }
-
-                shapes.insert(shape.name.clone(), shape.clone());
             }
-        };
-    }
-
-    if !found_main && error_missing_main {
-        return eval_error(MissingMain, program.end);
+        }
     }
 
-    Ok(shapes)
+    Ok(Value::Record(fields))
 }
 
-fn load_stdlib_shapes() -> EvalResult<HashMap<String, Shape>> {
-    let stdlib_input = stdlib::get_stdlib();
-
-    let tokens = match lexer::lex(&stdlib_input) {
-        Err(_) => return eval_error(StdLibNotLoaded("lexing".to_owned()), create_pos(0, 0)),
-        Ok(tokens) => tokens,
+fn eval_field(record: &Expr, name: &str, pos: Pos, ctx: &mut Context) -> EvalResult<Value> {
+    let fields = match eval_expression(record, ctx)? {
+        Value::Vec2(x, y, unit) => vec![
+            ("x".to_owned(), Value::Number(x, unit)),
+            ("y".to_owned(), Value::Number(y, unit)),
+        ],
+        value => get_record(value, record.pos())?,
     };
+    match get_field(&fields, name) {
+        Some(value) => Ok(value),
+        None => eval_error(FieldNotDefined(name.to_owned()), pos),
+    }
+}
 
-    let ast = match parser::parse_program(tokens) {
-        Err(_) => return eval_error(StdLibNotLoaded("parsing".to_owned()), create_pos(0, 0)),
-        Ok(ast) => ast,
+fn eval_index(list: &Expr, index_expr: &Expr, ctx: &mut Context) -> EvalResult<Value> {
+    let values = match eval_expression(list, ctx)? {
+        Value::List(values) => values,
+        v => return eval_error(TypeMismatch("list".to_owned(), v.name()), list.pos()),
     };
+    let index = get_number(eval_expression(index_expr, ctx)?, index_expr.pos())?;
+    let i = list_index(index.0, index.1, values.len(), index_expr.pos())?;
 
-    match find_shapes(HashMap::new(), &ast, false) {
-        Err(_) => {
-            return eval_error(
-                StdLibNotLoaded("finding shapes for".to_owned()),
-                create_pos(0, 0),
-            )
+    Ok(values[i].clone())
+}
+
+fn eval_interpolated(parts: &[InterpolatedPart], ctx: &mut Context) -> EvalResult<Value> {
+    let mut s = String::new();
+    for part in parts.iter() {
+        match part {
+            InterpolatedPart::Literal(literal) => s.push_str(literal),
+            InterpolatedPart::Expr(expr) => s.push_str(&format!("{}", eval_expression(expr, ctx)?)),
         }
-        Ok(shapes) => Ok(shapes),
     }
+    Ok(Value::String(s))
 }
 
-pub fn eval_program(program: &Program) -> EvalResult<String> {
-    let ctx = &mut Context::new();
+// A range counts from `start` up to, but not including, `end`. A negative
+// step counts down instead.
+fn eval_range(
+    start_expr: &Expr,
+    end_expr: &Expr,
+    step_expr: Option<&Expr>,
+    ctx: &mut Context,
+) -> EvalResult<Value> {
+    let start = get_number(eval_expression(start_expr, ctx)?, start_expr.pos())?;
+    let end = get_number(eval_expression(end_expr, ctx)?, end_expr.pos())?;
+
+    let (step, step_pos) = match step_expr {
+        None => ((1.0, None), end_expr.pos()),
+        Some(expr) => (
+            get_number(eval_expression(expr, ctx)?, expr.pos())?,
+            expr.pos(),
+        ),
+    };
 
-    let stdlib_shapes = load_stdlib_shapes()?;
+    range_list(
+        (start, start_expr.pos()),
+        (end, end_expr.pos()),
+        (step, step_pos),
+    )
+}
 
-    let shapes = find_shapes(stdlib_shapes, program, true)?;
-    ctx.shapes = shapes;
+// the numbers of a range along with where each one came from
+type RangeBound = ((f64, Option<Unit>), Pos);
 
-    let main = ctx.shapes.get("main").unwrap().clone();
+fn range_list(start: RangeBound, end: RangeBound, step: RangeBound) -> EvalResult<Value> {
+    let (start, start_pos) = start;
+    let (end, end_pos) = end;
+    let (step, step_pos) = step;
 
-    let main_svg = match eval_block(&main.block, ctx)? {
-        Value::String(value) => value,
-        _ => panic!("eval_block should return Value::String"),
-    };
+    let (start, end, unit) = unify_units(start, end, end_pos)?;
+    let (_, step, unit) = unify_units((start, unit), step, step_pos)?;
+
+    if step == 0.0 {
+        return eval_error(RangeStepZero, step_pos);
+    }
 
-    let wrapped_svg = format!(
-        "<svg width=\"100%\" height=\"100%\" xmlns=\"http://www.w3.org/2000/svg\">{}</svg>",
-        main_svg
-    );
+    let count = ((end - start) / step).ceil().max(0.0);
+    if !count.is_finite() || count > RANGE_LIMIT as f64 {
+        return eval_error(RangeTooLarge(RANGE_LIMIT), start_pos);
+    }
+
+    let values = (0..count as usize)
+        .map(|i| Value::Number(start + step * i as f64, unit))
+        .collect();
 
-    Ok(wrapped_svg)
+    Ok(Value::List(values))
 }
 
-#[cfg(test)]
-mod tests {
-    use insta::assert_debug_snapshot_matches;
+enum NativeFn {
+    Pure(fn(&Args) -> EvalResult<Value>),
+    // functions like `random` which need the state stored in the context
+    WithContext(fn(&Args, &mut Context) -> EvalResult<Value>),
+}
 
-    use super::*;
+struct Native {
+    name: &'static str,
+    min_args: usize,
+    max_args: usize,
+    func: NativeFn,
+}
 
-    fn check_expression(line: &str, expected: Value) {
-        let tokens = lexer::lex(&line.to_owned()).unwrap();
-        let expr = parser::parse_expression(tokens).unwrap();
-        let output = eval_expression(&expr, &mut Context::new()).unwrap();
+// functions implemented by the interpreter which can be called from expressions
+static NATIVE_FNS: &[Native] = &[
+    Native {
+        name: "rgb",
+        min_args: 3,
+        max_args: 4,
+        func: NativeFn::Pure(builtins::rgb),
+    },
+    Native {
+        name: "hsl",
+        min_args: 3,
+        max_args: 4,
+        func: NativeFn::Pure(builtins::hsl),
+    },
+    Native {
+        name: "mix",
+        min_args: 2,
+        max_args: 3,
+        func: NativeFn::Pure(builtins::mix),
+    },
+    Native {
+        name: "lighten",
+        min_args: 2,
+        max_args: 2,
+        func: NativeFn::Pure(builtins::lighten),
+    },
+    Native {
+        name: "darken",
+        min_args: 2,
+        max_args: 2,
+        func: NativeFn::Pure(builtins::darken),
+    },
+    Native {
+        name: "alpha",
+        min_args: 2,
+        max_args: 2,
+        func: NativeFn::Pure(builtins::alpha),
+    },
+    Native {
+        name: "sin",
+        min_args: 1,
+        max_args: 1,
+        func: NativeFn::Pure(builtins::sin),
+    },
+    Native {
+        name: "cos",
+        min_args: 1,
+        max_args: 1,
+        func: NativeFn::Pure(builtins::cos),
+    },
+    Native {
+        name: "tan",
+        min_args: 1,
+        max_args: 1,
+        func: NativeFn::Pure(builtins::tan),
+    },
+    Native {
+        name: "atan2",
+        min_args: 2,
+        max_args: 2,
+        func: NativeFn::Pure(builtins::atan2),
+    },
+    Native {
+        name: "sqrt",
+        min_args: 1,
+        max_args: 1,
+        func: NativeFn::Pure(builtins::sqrt),
+    },
+    Native {
+        name: "pow",
+        min_args: 2,
+        max_args: 2,
+        func: NativeFn::Pure(builtins::pow),
+    },
+    Native {
+        name: "abs",
+        min_args: 1,
+        max_args: 1,
+        func: NativeFn::Pure(builtins::abs),
+    },
+    Native {
+        name: "min",
+        min_args: 1,
+        max_args: usize::MAX,
+        func: NativeFn::Pure(builtins::min),
+    },
+    Native {
+        name: "max",
+        min_args: 1,
+        max_args: usize::MAX,
+        func: NativeFn::Pure(builtins::max),
+    },
+    Native {
+        name: "floor",
+        min_args: 1,
+        max_args: 1,
+        func: NativeFn::Pure(builtins::floor),
+    },
+    Native {
+        name: "ceil",
+        min_args: 1,
+        max_args: 1,
+        func: NativeFn::Pure(builtins::ceil),
+    },
+    Native {
+        name: "round",
+        min_args: 1,
+        max_args: 1,
+        func: NativeFn::Pure(builtins::round),
+    },
+    Native {
+        name: "clamp",
+        min_args: 3,
+        max_args: 3,
+        func: NativeFn::Pure(builtins::clamp),
+    },
+    Native {
+        name: "lerp",
+        min_args: 3,
+        max_args: 3,
+        func: NativeFn::Pure(builtins::lerp),
+    },
+    Native {
+        name: "map_range",
+        min_args: 5,
+        max_args: 5,
+        func: NativeFn::Pure(builtins::map_range),
+    },
+    Native {
+        name: "vec",
+        min_args: 2,
+        max_args: 2,
+        func: NativeFn::Pure(builtins::vec),
+    },
+    Native {
+        name: "length",
+        min_args: 1,
+        max_args: 1,
+        func: NativeFn::Pure(builtins::length),
+    },
+    Native {
+        name: "normalize",
+        min_args: 1,
+        max_args: 1,
+        func: NativeFn::Pure(builtins::normalize),
+    },
+    Native {
+        name: "dot",
+        min_args: 2,
+        max_args: 2,
+        func: NativeFn::Pure(builtins::dot),
+    },
+    Native {
+        name: "rotate",
+        min_args: 2,
+        max_args: 2,
+        func: NativeFn::Pure(builtins::rotate),
+    },
+    Native {
+        name: "polar",
+        min_args: 2,
+        max_args: 2,
+        func: NativeFn::Pure(builtins::polar),
+    },
+    Native {
+        name: "random",
+        min_args: 0,
+        max_args: 0,
+        func: NativeFn::WithContext(builtins::random),
+    },
+    Native {
+        name: "random_range",
+        min_args: 2,
+        max_args: 2,
+        func: NativeFn::WithContext(builtins::random_range),
+    },
+    Native {
+        name: "random_int",
+        min_args: 2,
+        max_args: 2,
+        func: NativeFn::WithContext(builtins::random_int),
+    },
+    Native {
+        name: "choose",
+        min_args: 1,
+        max_args: 1,
+        func: NativeFn::WithContext(builtins::choose),
+    },
+    Native {
+        name: "noise",
+        min_args: 2,
+        max_args: 3,
+        func: NativeFn::WithContext(builtins::noise),
+    },
+    Native {
+        name: "len",
+        min_args: 1,
+        max_args: 1,
+        func: NativeFn::Pure(builtins::len),
+    },
+    Native {
+        name: "push",
+        min_args: 2,
+        max_args: 2,
+        func: NativeFn::Pure(builtins::push),
+    },
+    Native {
+        name: "concat",
+        min_args: 1,
+        max_args: usize::MAX,
+        func: NativeFn::Pure(builtins::concat),
+    },
+    Native {
+        name: "range",
+        min_args: 1,
+        max_args: 3,
+        func: NativeFn::Pure(builtins::range),
+    },
+    Native {
+        name: "map",
+        min_args: 2,
+        max_args: 2,
+        func: NativeFn::WithContext(builtins::map),
+    },
+    Native {
+        name: "filter",
+        min_args: 2,
+        max_args: 2,
+        func: NativeFn::WithContext(builtins::filter),
+    },
+    Native {
+        name: "reverse",
+        min_args: 1,
+        max_args: 1,
+        func: NativeFn::Pure(builtins::reverse),
+    },
+    Native {
+        name: "sort",
+        min_args: 1,
+        max_args: 2,
+        func: NativeFn::WithContext(builtins::sort),
+    },
+    Native {
+        name: "zip",
+        min_args: 2,
+        max_args: 2,
+        func: NativeFn::Pure(builtins::zip),
+    },
+];
+
+// values that can be referred to by name unless shadowed
+static NATIVE_CONSTS: &[(&str, f64)] = &[("pi", PI), ("tau", 2.0 * PI)];
+
+// Lists can be indexed from the end with negative numbers, as in `xs[-1]`
+fn list_index(index: f64, unit: Option<Unit>, len: usize, pos: Pos) -> EvalResult<usize> {
+    if unit.is_some() {
+        return eval_error(TypeMismatch(unit_name(None), unit_name(unit)), pos);
+    }
+    if index.fract() != 0.0 {
+        return eval_error(InvalidIndex(index), pos);
+    }
 
-        assert_eq!(output, expected);
+    let i = if index < 0.0 {
+        index + len as f64
+    } else {
+        index
+    };
+    if i < 0.0 || i >= len as f64 {
+        return eval_error(IndexOutOfBounds(index, len), pos);
     }
 
-    fn run_program(line: &str) -> EvalResult<String> {
-        let tokens = lexer::lex(&line.to_owned()).unwrap();
-        let program = parser::parse_program(tokens).unwrap();
+    Ok(i as usize)
+}
 
-        let ctx = &mut Context::new();
-        eval_program(&program)
-    }
+// Call a user function, a native function or a variable holding either one.
+fn call_function(name: &str, args: &Args, pos: Pos, ctx: &mut Context) -> EvalResult<Value> {
+    let name = match ctx.get(name) {
+        Some(Value::Function(target)) => target,
+        _ => name.to_owned(),
+    };
 
-    #[test]
-    fn context_empty() {
-        let context = Context::new();
-        assert_eq!(context.scope.len(), 0);
+    match ctx.get_function(&name) {
+        Some(function) => {
+            let function = function.clone();
+            eval_function_call(&function, args, pos, ctx)
+        }
+        None => eval_native_call(&name, args, pos, ctx),
     }
+}
 
-    #[test]
-    fn adding_item_to_context() {
-        let mut context = Context::new();
-        context.set("foo", Value::from_string("bar"));
-        assert_eq!(context.get("foo"), Some(Value::from_string("bar")));
+fn eval_function_call(
+    function: &Function,
+    args: &Args,
+    pos: Pos,
+    ctx: &mut Context,
+) -> EvalResult<Value> {
+    let received = args.len();
+    let required = function
+        .args
+        .iter()
+        .filter(|arg| arg.default.is_none())
+        .count();
+
+    if received < required || received > function.args.len() {
+        let expected = if received < required {
+            required
+        } else {
+            function.args.len()
+        };
+        return eval_error(NumArgs(function.name.clone(), expected, received), pos);
     }
 
-    #[test]
-    fn literals() {
-        check_expression("1", Value::Number(1.0));
+    // like shapes, functions only see their args and the root scope
+    let mut fn_ctx = Context::with_parent(ctx.root());
+    fn_ctx.stack = ctx.stack.clone();
+    fn_ctx.stack.push(function.name.clone());
+    if fn_ctx.stack.len() > STACK_LIMIT {
+        return eval_error(StackOverflow(fn_ctx.stack.clone()), pos);
     }
 
-    #[test]
-    fn binary_ops() {
-        check_expression("1 + 1", Value::Number(2.0));
-        check_expression("1.5 + 1", Value::Number(2.5));
-        check_expression("2 * 2", Value::Number(4.0));
-        check_expression("4 / 2", Value::Number(2.0));
-        check_expression("6 - 3", Value::Number(3.0));
-        check_expression("3 * (2 + -4) / 2 * 3", Value::Number(-9.0));
-        check_expression("3 + \"hello\"", Value::String("3hello".to_owned()))
+    for (i, arg) in function.args.iter().enumerate() {
+        let value = match &arg.default {
+            _ if i < received => args.value(i),
+            Some(expr) => eval_expression(expr, &mut fn_ctx)?,
+            None => unreachable!(),
+        };
+
+        fn_ctx.set(&arg.name, value);
     }
 
-    #[test]
-    fn simple_find_shapes() {
-        let line = "
-shape main() {}
-shape circle(r) {}
-shape rect(w, h) {}
-";
-        let tokens = lexer::lex(&line.to_owned()).unwrap();
-        let program = parser::parse_program(tokens).unwrap();
+    eval_expression(&function.expr, &mut fn_ctx)
+}
 
-        let shapes = find_shapes(HashMap::new(), &program, true).unwrap();
+fn is_native_fn(name: &str) -> bool {
+    NATIVE_FNS.iter().any(|native| native.name == name)
+}
 
-        assert_eq!(shapes.len(), 3)
-    }
+fn eval_native_call(name: &str, args: &Args, pos: Pos, ctx: &mut Context) -> EvalResult<Value> {
+    let native = match NATIVE_FNS.iter().find(|native| native.name == name) {
+        Some(native) => native,
+        None => return eval_error(FunctionNotDefined(name.to_owned()), pos),
+    };
 
-    #[test]
+    let received = args.len();
+    if received < native.min_args || received > native.max_args {
+        let expected = if received < native.min_args {
+            native.min_args
+        } else {
+            native.max_args
+        };
+        return eval_error(NumArgs(name.to_owned(), expected, received), pos);
+    }
+
+    let value = match native.func {
+        NativeFn::Pure(func) => func(args)?,
+        NativeFn::WithContext(func) => func(args, ctx)?,
+    };
+    check_finite(value, pos)
+}
+
+// raw_svg(value: "<path d='...'/>") is written to the output as is, so it is
+// only allowed when the caller opts in
+fn eval_raw_svg_call(call: &FunCall, ctx: &mut Context) -> EvalResult<Vec<Node>> {
+    if !ctx.root().raw_svg {
+        return eval_error(RawSvgDisabled, call.pos());
+    }
+
+    if call.args.len() != 1 {
+        return eval_error(NumArgs(call.ident.clone(), 1, call.args.len()), call.pos());
+    }
+
+    let arg = &call.args[0];
+    if arg.name != "value" {
+        return eval_error(
+            MissingArgs(call.ident.clone(), vec!["value".to_owned()]),
+            call.pos(),
+        );
+    }
+
+    let value = eval_expression(&arg.expr, ctx)?;
+
+    let value = match get_string(value, arg.expr.pos()) {
+        Err(e) => match &e.error_type {
+            TypeMismatch(_, received) => {
+                return eval_error(SvgExpectsString(received.clone()), e.pos())
+            }
+            error_type => return eval_error(error_type.clone(), e.pos()),
+        },
+        Ok(v) => v,
+    };
+
+    Ok(vec![Node::Raw(value, call.range)])
+}
+
+// fields of spread records fill in args which are not given by name
+fn eval_spreads(call: &FunCall, ctx: &mut Context) -> EvalResult<Vec<(String, Value)>> {
+    let mut spread: Vec<(String, Value)> = vec![];
+    for expr in call.spreads.iter() {
+        let value = eval_expression(expr, ctx)?;
+        for (key, value) in get_record(value, expr.pos())?.into_iter() {
+            set_field(&mut spread, &key, value);
+        }
+    }
+
+    Ok(spread)
+}
+
+// The named and spread args of a builtin call, which can only use the names in
+// `allowed`
+fn eval_builtin_args(
+    call: &FunCall,
+    allowed: &[&str],
+    ctx: &mut Context,
+) -> EvalResult<Vec<(String, Value)>> {
+    let mut args = eval_spreads(call, ctx)?;
+    for arg in call.args.iter() {
+        let value = eval_expression(&arg.expr, ctx)?;
+        set_field(&mut args, &arg.name, value);
+    }
+
+    if let Some((name, _)) = args
+        .iter()
+        .find(|(name, _)| !allowed.contains(&name.as_str()))
+    {
+        return eval_error(UnExpectedArg(call.ident.clone(), name.clone()), call.pos());
+    }
+
+    Ok(args)
+}
+
+// The text of an attribute. Lists are separated by spaces, as in `points` or
+// `stroke-dasharray`, while records and functions have no sensible
+// representation so they are rejected.
+fn attr_value(value: Value, pos: Pos) -> EvalResult<String> {
+    match value {
+        Value::List(values) => {
+            let values = values
+                .into_iter()
+                .map(|value| attr_value(value, pos))
+                .collect::<EvalResult<Vec<String>>>()?;
+            Ok(values.join(" "))
+        }
+        Value::Record(_) | Value::Function(_) => eval_error(
+            TypeMismatch("attribute value".to_owned(), value.name()),
+            pos,
+        ),
+        value => Ok(value.to_string()),
+    }
+}
+
+// element(name: "circle", attrs: {r: 10}) { ... } creates an svg element with
+// the nodes of its block as children. An optional `text` arg is added as
+// escaped text before the children.
+fn eval_element_call(call: &FunCall, ctx: &mut Context) -> EvalResult<Vec<Node>> {
+    let args = eval_builtin_args(call, &["name", "attrs", "text"], ctx)?;
+
+    let name = match get_field(&args, "name") {
+        Some(value) => get_string(value, call.pos())?,
+        None => {
+            return eval_error(
+                MissingRequiredArg(call.ident.clone(), "name".to_owned()),
+                call.pos(),
+            )
+        }
+    };
+    if !svg::is_svg_element(&name) {
+        return eval_error(UnknownElement(name), call.pos());
+    }
+
+    let mut element = Element::new(&name, call.range);
+    if let Some(attrs) = get_field(&args, "attrs") {
+        for (key, value) in get_record(attrs, call.pos())?.into_iter() {
+            if !svg::is_svg_attribute(&key) {
+                return eval_error(UnknownAttribute(name, key), call.pos());
+            }
+
+            // `false` leaves the attribute out, for optional style args
+            if value == Value::Bool(false) {
+                continue;
+            }
+
+            let value = attr_value(value, call.pos())?;
+            if svg::is_unsafe_value(&key, &value) {
+                return eval_error(UnsafeAttrValue(key), call.pos());
+            }
+
+            element.set_attr(&key, value);
+        }
+    }
+
+    if let Some(text) = get_field(&args, "text") {
+        let text = attr_value(text, call.pos())?;
+        element.children.push(Node::Text(text, call.range));
+    }
+
+    if let Some(children) = &call.children {
+        element.children.extend(eval_block(children, ctx)?);
+    }
+
+    Ok(vec![Node::Element(element)])
+}
+
+// group(id: "a", class: "b", opacity: 50%, z: 1) { ... } puts its children in
+// a `<g>` which is drawn in front of siblings with a lower z
+fn eval_group_call(call: &FunCall, ctx: &mut Context) -> EvalResult<Vec<Node>> {
+    let args = eval_builtin_args(call, &["id", "class", "opacity", "z"], ctx)?;
+
+    let mut group = Element::new("g", call.range);
+    for name in ["id", "class"].iter() {
+        if let Some(value) = get_field(&args, name) {
+            group.set_attr(name, attr_value(value, call.pos())?);
+        }
+    }
+
+    if let Some(value) = get_field(&args, "opacity") {
+        let opacity = match get_number(value, call.pos())? {
+            (n, None) => n,
+            (n, Some(Unit::Percent)) => n / 100.0,
+            (_, unit) => {
+                return eval_error(
+                    TypeMismatch("number or percentage".to_owned(), unit_name(unit)),
+                    call.pos(),
+                )
+            }
+        };
+        group.set_attr("opacity", opacity.to_string());
+    }
+
+    if let Some(value) = get_field(&args, "z") {
+        group.z = match get_number(value, call.pos())? {
+            (n, None) => n,
+            (_, unit) => {
+                return eval_error(TypeMismatch(unit_name(None), unit_name(unit)), call.pos())
+            }
+        };
+    }
+
+    match &call.children {
+        Some(block) => group.children = eval_block(block, ctx)?,
+        None => return eval_error(MissingChildren(call.ident.clone()), call.pos()),
+    }
+
+    Ok(vec![Node::Element(group)])
+}
+
+// calls that are handled before looking up shapes, so no shape or function
+// can be declared with these names
+static BUILTIN_SHAPES: &[&str] = &["element", "group", "raw_svg"];
+
+fn is_builtin_shape(name: &str) -> bool {
+    BUILTIN_SHAPES.contains(&name) || transform::is_transform(name)
+}
+
+fn eval_call(call: &FunCall, ctx: &mut Context) -> EvalResult<Vec<Node>> {
+    if call.ident == "element" {
+        return eval_element_call(call, ctx);
+    }
+
+    if call.ident == "group" {
+        return eval_group_call(call, ctx);
+    }
+
+    if transform::is_transform(&call.ident) {
+        return transform::eval_transform_call(call, ctx);
+    }
+
+    if call.children.is_some() {
+        return eval_error(UnExpectedChildren(call.ident.clone()), call.pos());
+    }
+
+    if call.ident == "raw_svg" {
+        return eval_raw_svg_call(call, ctx);
+    }
+
+    let shape = match ctx.get_shape(&call.ident) {
+        Some(shape) => shape.clone(),
+        None if ctx.get_function(&call.ident).is_some() => {
+            return eval_error(FunctionUsedAsShape(call.ident.clone()), call.pos())
+        }
+        None => return eval_error(ShapeNotDefined(call.ident.clone()), call.pos()),
+    };
+
+    let spread = eval_spreads(call, ctx)?;
+    let mut given: Vec<Option<Value>> = vec![];
+    for shape_arg in shape.args.iter() {
+        let value = match call.args.iter().find(|arg| arg.name == shape_arg.name) {
+            Some(call_arg) => Some(eval_expression(&call_arg.expr, ctx)?),
+            None => get_field(&spread, &shape_arg.name),
+        };
+
+        given.push(value);
+    }
+
+    // shapes only see their args and the root scope, not the caller's scope
+    let mut shape_ctx = Context::with_parent(ctx.root());
+    shape_ctx.stack = ctx.stack.clone();
+    shape_ctx.stack.push(call.ident.clone());
+    if shape_ctx.stack.len() > STACK_LIMIT {
+        return eval_error(StackOverflow(shape_ctx.stack.clone()), call.pos());
+    }
+
+    // defaults are evaluated in the shape's scope so they can use earlier args
+    for (shape_arg, value) in shape.args.iter().zip(given) {
+        let value = match (value, &shape_arg.default) {
+            (Some(value), _) => value,
+            (None, Some(default_expr)) => eval_expression(default_expr, &mut shape_ctx)?,
+            (None, None) => {
+                return eval_error(
+                    MissingRequiredArg(call.ident.clone(), shape_arg.name.clone()),
+                    call.pos(),
+                )
+            }
+        };
+
+        shape_ctx.set(&shape_arg.name, value);
+    }
+
+    eval_block(&shape.block, &mut shape_ctx)
+}
+
+// Every block gets its own scope so `let` bindings go away at the end of it
+fn eval_block(block: &Block, ctx: &mut Context) -> EvalResult<Vec<Node>> {
+    let mut nodes: Vec<Node> = vec![];
+    let block_ctx = &mut Context::with_parent(ctx);
+
+    for stmt in block.stmts.iter() {
+        nodes.extend(eval_stmt(stmt, block_ctx)?);
+    }
+
+    Ok(nodes)
+}
+
+fn eval_stmt(stmt: &Stmt, ctx: &mut Context) -> EvalResult<Vec<Node>> {
+    match stmt {
+        Stmt::Call(call) => eval_call(call, ctx),
+        Stmt::If(cond, then_block, else_block, _) => {
+            if get_bool(eval_expression(cond, ctx)?, cond.pos())? {
+                eval_block(then_block, ctx)
+            } else if let Some(else_block) = else_block {
+                eval_block(else_block, ctx)
+            } else {
+                Ok(vec![])
+            }
+        }
+        // a later let with the same name shadows the earlier binding
+        Stmt::Let(name, expr, _) => {
+            let value = eval_expression(expr, ctx)?;
+            ctx.set(name, value);
+
+            Ok(vec![])
+        }
+        Stmt::For(name, iter, block, _) => {
+            let values = match eval_expression(iter, ctx)? {
+                Value::List(values) => values,
+                v => return eval_error(TypeMismatch("list".to_owned(), v.name()), iter.pos()),
+            };
+
+            let mut nodes = vec![];
+            for value in values.into_iter() {
+                let mut loop_ctx = Context::with_parent(ctx);
+                loop_ctx.set(name, value);
+
+                nodes.extend(eval_block(block, &mut loop_ctx)?);
+            }
+
+            Ok(nodes)
+        }
+    }
+}
+
+fn find_shapes(
+    shapes: HashMap<String, Shape>,
+    program: &Program,
+    error_missing_main: bool,
+) -> EvalResult<HashMap<String, Shape>> {
+    let mut shapes: HashMap<String, Shape> = shapes.clone();
+    let mut found_main = false;
+
+    for decl in program.decls.iter() {
+        match &decl {
+            Decl::ShapeDecl(shape) => {
+                if shapes.contains_key(&shape.name) || is_builtin_shape(&shape.name) {
+                    return eval_error(ShapeAlreadyDefined(shape.name.clone()), shape.pos());
+                }
+
+                if shape.name == "main" {
+                    found_main = true;
+                }
+
+                shapes.insert(shape.name.clone(), shape.clone());
+            }
+            Decl::ConstDecl(_)
+            | Decl::FnDecl(_)
+            | Decl::SeedDecl(_, _)
+            | Decl::CanvasDecl(_, _)
+            | Decl::LayerDecl(_) => (),
+        };
+    }
+
+    if !found_main && error_missing_main {
+        return eval_error(MissingMain, program.end);
+    }
+
+    Ok(shapes)
+}
+
+// Consts are evaluated in source order into the root scope and can only refer
+// to consts declared above them. A reference to a later const is followed
+// through `evaluating` to tell a `CyclicConst` from a use before definition.
+fn eval_const<'a>(
+    consts: &[&'a Const],
+    index: usize,
+    evaluating: &mut Vec<&'a str>,
+    ctx: &mut Context,
+) -> EvalResult<Value> {
+    let constant = consts[index];
+    evaluating.push(&constant.name);
+
+    let result = match eval_expression(&constant.expr, ctx) {
+        Err(EvalError {
+            error_type: VariableNotDefined(name),
+            pos,
+        }) => {
+            let later = consts[index..].iter().position(|c| c.name == name);
+
+            if evaluating.contains(&name.as_str()) {
+                eval_error(CyclicConst(name), pos)
+            } else if let Some(offset) = later {
+                match eval_const(consts, index + offset, evaluating, ctx) {
+                    Err(EvalError {
+                        error_type: CyclicConst(name),
+                        pos,
+                    }) => eval_error(CyclicConst(name), pos),
+                    _ => eval_error(ConstUsedBeforeDefinition(name), pos),
+                }
+            } else {
+                eval_error(VariableNotDefined(name), pos)
+            }
+        }
+        result => result,
+    };
+
+    evaluating.pop();
+    result
+}
+
+fn eval_consts(program: &Program, ctx: &mut Context) -> EvalResult<()> {
+    let consts: Vec<&Const> = program
+        .decls
+        .iter()
+        .filter_map(|decl| match decl {
+            Decl::ConstDecl(constant) => Some(constant),
+            _ => None,
+        })
+        .collect();
+
+    for (i, constant) in consts.iter().enumerate() {
+        if consts[..i].iter().any(|c| c.name == constant.name) {
+            return eval_error(ConstAlreadyDefined(constant.name.clone()), constant.pos());
+        }
+
+        let value = eval_const(&consts, i, &mut vec![], ctx)?;
+        ctx.set(&constant.name, value);
+    }
+
+    Ok(())
+}
+
+fn find_functions(program: &Program) -> EvalResult<HashMap<String, Function>> {
+    let mut functions: HashMap<String, Function> = HashMap::new();
+
+    for decl in program.decls.iter() {
+        if let Decl::FnDecl(function) = decl {
+            if functions.contains_key(&function.name) || is_builtin_shape(&function.name) {
+                return eval_error(
+                    FunctionAlreadyDefined(function.name.clone()),
+                    function.pos(),
+                );
+            }
+
+            functions.insert(function.name.clone(), function.clone());
+        }
+    }
+
+    Ok(functions)
+}
+
+// Layers are drawn after `main` in the order they are declared, each as a
+// top-level group tagged with its name so tools can find them.
+fn eval_layers(program: &Program, ctx: &mut Context) -> EvalResult<Vec<Node>> {
+    let mut names: Vec<&str> = vec![];
+    let mut nodes: Vec<Node> = vec![];
+
+    for decl in program.decls.iter() {
+        if let Decl::LayerDecl(layer) = decl {
+            if names.contains(&layer.name.as_str()) {
+                return eval_error(LayerAlreadyDefined(layer.name.clone()), layer.pos());
+            }
+            names.push(&layer.name);
+
+            let mut group = Element::new("g", layer.block.range);
+            group.set_attr("data-layer", layer.name.clone());
+            // stay after main whatever the z of its children
+            group.z = f64::INFINITY;
+            group.children = eval_block(&layer.block, ctx)?;
+            nodes.push(Node::Element(group));
+        }
+    }
+
+    Ok(nodes)
+}
+
+fn load_stdlib_shapes() -> EvalResult<HashMap<String, Shape>> {
+    let stdlib_input = stdlib::get_stdlib();
+
+    let tokens = match lexer::lex(&stdlib_input) {
+        Err(_) => return eval_error(StdLibNotLoaded("lexing".to_owned()), create_pos(0, 0)),
+        Ok(tokens) => tokens,
+    };
+
+    let ast = match parser::parse_program(tokens) {
+        Err(_) => return eval_error(StdLibNotLoaded("parsing".to_owned()), create_pos(0, 0)),
+        Ok(ast) => ast,
+    };
+
+    match find_shapes(HashMap::new(), &ast, false) {
+        Err(_) => {
+            return eval_error(
+                StdLibNotLoaded("finding shapes for".to_owned()),
+                create_pos(0, 0),
+            )
+        }
+        Ok(mut shapes) => {
+            shapes.values_mut().for_each(stdlib::add_style_args);
+            Ok(shapes)
+        }
+    }
+}
+
+/// Settings that come from the caller of the compiler rather than the program.
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    /// Overrides the `seed` declared in the program.
+    pub seed: Option<f64>,
+    /// Allows `raw_svg(value: ...)`, which writes markup without escaping it.
+    pub raw_svg: bool,
+    /// Overrides the values of the `canvas` declared in the program.
+    pub canvas: Canvas,
+}
+
+// The seed is evaluated before anything else so consts can use random values.
+// Numbers are turned into seeds by their bits so every number is distinct.
+fn find_seed(program: &Program, options: &Options) -> EvalResult<u64> {
+    let mut seed: Option<(f64, Pos)> = None;
+
+    for decl in program.decls.iter() {
+        if let Decl::SeedDecl(expr, pos) = decl {
+            if seed.is_some() {
+                return eval_error(SeedAlreadyDefined, *pos);
+            }
+
+            let value = eval_expression(expr, &mut Context::new())?;
+            let n = match get_number(value, expr.pos())? {
+                (n, None) => n,
+                (_, unit) => {
+                    return eval_error(TypeMismatch(unit_name(None), unit_name(unit)), expr.pos())
+                }
+            };
+            seed = Some((n, *pos));
+        }
+    }
+
+    Ok(match options.seed.or(seed.map(|(n, _)| n)) {
+        Some(n) => n.to_bits(),
+        None => DEFAULT_SEED,
+    })
+}
+
+/// Evaluate a program into the root `<svg>` element of the drawing.
+pub fn eval_document(program: &Program, options: &Options) -> EvalResult<Element> {
+    let ctx = &mut Context::new();
+    ctx.random = Some(Random::new(find_seed(program, options)?));
+    ctx.raw_svg = options.raw_svg;
+
+    let stdlib_shapes = load_stdlib_shapes()?;
+
+    let shapes = find_shapes(stdlib_shapes, program, true)?;
+    ctx.shapes = shapes;
+    ctx.functions = find_functions(program)?;
+
+    eval_consts(program, ctx)?;
+
+    let main = ctx.shapes.get("main").unwrap().clone();
+
+    let canvas = canvas::find_canvas(program, options, ctx)?;
+
+    let mut svg = canvas.element(main.block.range);
+    svg.children.extend(eval_block(&main.block, ctx)?);
+    svg.children.extend(eval_layers(program, ctx)?);
+
+    Ok(svg)
+}
+
+pub fn eval_program(program: &Program, options: &Options) -> EvalResult<String> {
+    Ok(eval_document(program, options)?.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use insta::assert_debug_snapshot_matches;
+
+    use super::*;
+
+    fn check_expression(line: &str, expected: Value) {
+        let tokens = lexer::lex(&line.to_owned()).unwrap();
+        let expr = parser::parse_expression(tokens).unwrap();
+        let output = eval_expression(&expr, &mut Context::new()).unwrap();
+
+        assert_eq!(output, expected);
+    }
+
+    fn check_expression_error(line: &str, expected: EvalErrorType, pos: Pos) {
+        let tokens = lexer::lex(&line.to_owned()).unwrap();
+        let expr = parser::parse_expression(tokens).unwrap();
+        let output = eval_expression(&expr, &mut Context::new());
+
+        assert_eq!(output, Err(EvalError::new(expected, pos)), "{}", line);
+    }
+
+    // most tests print values with raw_svg so it is enabled here
+    fn run_program(line: &str) -> EvalResult<String> {
+        let options = Options {
+            raw_svg: true,
+            ..Options::default()
+        };
+        run_program_with_options(line, &options)
+    }
+
+    fn run_program_with_options(line: &str, options: &Options) -> EvalResult<String> {
+        let tokens = lexer::lex(&line.to_owned()).unwrap();
+        let program = parser::parse_program(tokens).unwrap();
+
+        eval_program(&program, options)
+    }
+
+    #[test]
+    fn context_empty() {
+        let context = Context::new();
+        assert_eq!(context.scope.len(), 0);
+    }
+
+    #[test]
+    fn adding_item_to_context() {
+        let mut context = Context::new();
+        context.set("foo", Value::from_string("bar"));
+        assert_eq!(context.get("foo"), Some(Value::from_string("bar")));
+    }
+
+    #[test]
+    fn literals() {
+        check_expression("1", Value::from_number(1.0));
+    }
+
+    #[test]
+    fn binary_ops() {
+        check_expression("1 + 1", Value::from_number(2.0));
+        check_expression("1.5 + 1", Value::from_number(2.5));
+        check_expression("2 * 2", Value::from_number(4.0));
+        check_expression("4 / 2", Value::from_number(2.0));
+        check_expression("6 - 3", Value::from_number(3.0));
+        check_expression("3 * (2 + -4) / 2 * 3", Value::from_number(-9.0));
+        check_expression("3 + \"hello\"", Value::String("3hello".to_owned()))
+    }
+
+    #[test]
+    fn unit_ops() {
+        check_expression("10px + 5", Value::Number(15.0, Some(Unit::Px)));
+        check_expression("2 * 25%", Value::Number(50.0, Some(Unit::Percent)));
+        check_expression("-45deg", Value::Number(-45.0, Some(Unit::Deg)));
+        check_expression("1turn - 180deg", Value::Number(0.5, Some(Unit::Turn)));
+        check_expression("1turn / 90deg", Value::from_number(4.0));
+        check_expression("3 + \"px\"", Value::String("3px".to_owned()));
+    }
+
+    #[test]
+    fn add_only_concatenates_strings() {
+        check_expression("\"#\" + 1", Value::from_string("#1"));
+        check_expression("\"fill \" + #fff", Value::from_string("fill #ffffff"));
+        check_expression_error(
+            "#fff + 1",
+            TypeMismatch("number".to_owned(), "color".to_owned()),
+            create_pos(0, 0),
+        );
+        check_expression_error(
+            "true + [1]",
+            TypeMismatch("number".to_owned(), "bool".to_owned()),
+            create_pos(0, 0),
+        );
+        check_expression_error(
+            "1 + [1]",
+            TypeMismatch("number".to_owned(), "list".to_owned()),
+            create_pos(0, 4),
+        );
+        check_expression_error(
+            "{a: 1} + 1",
+            TypeMismatch("number".to_owned(), "record".to_owned()),
+            create_pos(0, 0),
+        );
+    }
+
+    #[test]
+    fn mixed_unit_ops() {
+        check_expression_error(
+            "10px + 5%",
+            TypeMismatch("number in px".to_owned(), "number in %".to_owned()),
+            create_pos(0, 7),
+        );
+        check_expression_error(
+            "10deg - 1px",
+            TypeMismatch("number in deg".to_owned(), "number in px".to_owned()),
+            create_pos(0, 8),
+        );
+        check_expression_error(
+            "2px * 2px",
+            TypeMismatch("number".to_owned(), "number in px".to_owned()),
+            create_pos(0, 6),
+        );
+        check_expression_error(
+            "2 / 1rad",
+            TypeMismatch("number".to_owned(), "number in rad".to_owned()),
+            create_pos(0, 4),
+        );
+    }
+
+    #[test]
+    fn color_literals() {
+        check_expression("#f00", Value::Color(Color::new(1.0, 0.0, 0.0, 1.0)));
+        check_expression("#00ff0000", Value::Color(Color::new(0.0, 1.0, 0.0, 0.0)));
+        check_expression("\"fill: \" + #ff69b4", Value::from_string("fill: #ff69b4"));
+    }
+
+    #[test]
+    fn color_functions() {
+        let check = |line: &str, expected: &str| {
+            let tokens = lexer::lex(&line.to_owned()).unwrap();
+            let expr = parser::parse_expression(tokens).unwrap();
+            let output = eval_expression(&expr, &mut Context::new()).unwrap();
+
+            assert_eq!(format!("{}", output), expected);
+        };
+
+        check("rgb(255, 0, 50%)", "#ff0080");
+        check("rgb(0, 0, 255, 0.45)", "rgba(0, 0, 255, 0.45)");
+        check("hsl(120deg, 100%, 50%)", "#00ff00");
+        check("hsl(0.5turn, 1, 0.5)", "#00ffff");
+        check("mix(#000, #fff)", "#808080");
+        check("mix(#f00, #00f, 25%)", "#bf0040");
+        check("lighten(#800000, 20%)", "#e60000");
+        check("darken(#ff0000, 10%)", "#cc0000");
+        check("alpha(#fff, 50%)", "rgba(255, 255, 255, 0.5)");
+    }
+
+    #[test]
+    fn color_function_errors() {
+        check_expression_error(
+            "rgb(1, 2)",
+            NumArgs("rgb".to_owned(), 3, 2),
+            create_pos(0, 0),
+        );
+        check_expression_error(
+            "mix(#fff, 1)",
+            TypeMismatch("color".to_owned(), "number".to_owned()),
+            create_pos(0, 10),
+        );
+        check_expression_error(
+            "hsl(1px, 1, 1)",
+            TypeMismatch("angle".to_owned(), "number in px".to_owned()),
+            create_pos(0, 4),
+        );
+        check_expression_error(
+            "nope(1)",
+            FunctionNotDefined("nope".to_owned()),
+            create_pos(0, 0),
+        );
+    }
+
+    #[test]
+    fn math_functions() {
+        let check = |line: &str, expected: f64| {
+            let tokens = lexer::lex(&line.to_owned()).unwrap();
+            let expr = parser::parse_expression(tokens).unwrap();
+            match eval_expression(&expr, &mut Context::new()).unwrap() {
+                Value::Number(n, _) => assert!((n - expected).abs() < 1e-9, "{} = {}", line, n),
+                v => panic!("{} returned {:?}", line, v),
+            }
+        };
+
+        check("sin(pi / 2)", 1.0);
+        check("cos(180deg)", -1.0);
+        check("tan(0.125turn)", 1.0);
+        check("atan2(1, 1)", PI / 4.0);
+        check("atan2(1, 0)", PI / 2.0);
+        check("atan2(0, -1)", PI);
+        check("sqrt(16)", 4.0);
+        check("pow(2, 10)", 1024.0);
+        check("tau", 2.0 * PI);
+        check("round(2.5) + floor(-0.5) + ceil(0.2)", 3.0);
+        check("min(3, 1, 2) + max(3, 1, 2)", 4.0);
+
+        check_expression("abs(-5px)", Value::Number(5.0, Some(Unit::Px)));
+        check_expression(
+            "clamp(120%, 0, 100%)",
+            Value::Number(100.0, Some(Unit::Percent)),
+        );
+        check_expression("max(1turn, 90deg)", Value::Number(1.0, Some(Unit::Turn)));
+        check_expression("lerp(10px, 20px, 25%)", Value::Number(12.5, Some(Unit::Px)));
+        check_expression("map_range(5, 0, 10, 100, 200)", Value::from_number(150.0));
+    }
+
+    #[test]
+    fn math_function_errors() {
+        check_expression_error("sin()", NumArgs("sin".to_owned(), 1, 0), create_pos(0, 0));
+        check_expression_error(
+            "clamp(1, 2)",
+            NumArgs("clamp".to_owned(), 3, 2),
+            create_pos(0, 0),
+        );
+        check_expression_error(
+            "sin(10px)",
+            TypeMismatch("angle".to_owned(), "number in px".to_owned()),
+            create_pos(0, 4),
+        );
+        check_expression_error(
+            "sqrt(\"4\")",
+            TypeMismatch("number".to_owned(), "string".to_owned()),
+            create_pos(0, 5),
+        );
+        check_expression_error(
+            "min(1px, 2%)",
+            TypeMismatch("number in px".to_owned(), "number in %".to_owned()),
+            create_pos(0, 9),
+        );
+        check_expression_error("sqrt(-1)", NonFiniteResult, create_pos(0, 0));
+        check_expression_error(
+            "map_range(1, 0, 0, 0, 1)",
+            NonFiniteResult,
+            create_pos(0, 0),
+        );
+    }
+
+    #[test]
+    fn random_functions() {
+        let check = |line: &str| {
+            let tokens = lexer::lex(&line.to_owned()).unwrap();
+            let expr = parser::parse_expression(tokens).unwrap();
+            eval_expression(&expr, &mut Context::new()).unwrap()
+        };
+
+        for _ in 0..2 {
+            assert_eq!(
+                check("random()"),
+                check("random()"),
+                "same seed, same value"
+            );
+        }
+
+        match check("random_range(10px, 20px)") {
+            Value::Number(n, Some(Unit::Px)) => assert!((10.0..20.0).contains(&n)),
+            v => panic!("random_range returned {:?}", v),
+        }
+        match check("random_int(-2, 2)") {
+            Value::Number(n, None) => assert!(n.fract() == 0.0 && (-2.0..2.0).contains(&n)),
+            v => panic!("random_int returned {:?}", v),
+        }
+        match check("choose(0..3)") {
+            Value::Number(n, None) => assert!(n == 0.0 || n == 1.0 || n == 2.0),
+            v => panic!("choose returned {:?}", v),
+        }
+        match check("noise(0.5, 1.5) + noise(1, 2, 3.5)") {
+            Value::Number(n, None) => assert!(n.abs() <= 2.0),
+            v => panic!("noise returned {:?}", v),
+        }
+        assert_eq!(check("noise(1, 2)"), Value::from_number(0.0));
+    }
+
+    #[test]
+    fn random_function_errors() {
+        check_expression_error("random_int(2, 2)", EmptyRange, create_pos(0, 14));
+        check_expression_error("choose(0..0)", EmptyList, create_pos(0, 8));
+        check_expression_error(
+            "choose(1)",
+            TypeMismatch("list".to_owned(), "number".to_owned()),
+            create_pos(0, 7),
+        );
+        check_expression_error(
+            "random(1)",
+            NumArgs("random".to_owned(), 0, 1),
+            create_pos(0, 0),
+        );
+    }
+
+    #[test]
+    fn eval_program_with_seed() {
+        let line = "
+seed 42
+const OFFSET = random_int(0, 100)
+
+shape main() {
+  for i in 0..3 {
+    raw_svg(value: \"${OFFSET + random_int(0, 10)} \")
+  }
+  raw_svg(value: \"${choose(OFFSET > 50 ? 0..5 : 5..10)}\")
+}
+";
+        let value = run_program(line).unwrap();
+        assert_eq!(value, run_program(line).unwrap());
+        assert_debug_snapshot_matches!(value)
+    }
+
+    #[test]
+    fn seed_option_overrides_program() {
+        let run = |line: &str, seed: Option<f64>| {
+            let options = Options {
+                seed,
+                raw_svg: true,
+                ..Options::default()
+            };
+            run_program_with_options(line, &options).unwrap()
+        };
+
+        let program = |seed: &str| {
+            format!(
+                "{}\nshape main() {{ raw_svg(value: \"${{random()}}\") }}",
+                seed
+            )
+        };
+
+        assert_eq!(run(&program("seed 1"), None), run(&program(""), Some(1.0)));
+        assert_eq!(
+            run(&program("seed 1"), Some(2.0)),
+            run(&program("seed 2"), None)
+        );
+        assert_ne!(run(&program("seed 1"), None), run(&program("seed 2"), None));
+    }
+
+    #[test]
+    fn eval_program_with_canvas() {
+        let line = "
+const SIZE = 200
+canvas { width: SIZE * 2, height: SIZE, background: #fff }
+
+shape main() {
+  circle(cx: 100, cy: 100)
+}
+";
+        let value = run_program(line).unwrap();
+        assert_debug_snapshot_matches!(value)
+    }
+
+    #[test]
+    fn canvas_defaults_fill_the_container() {
+        let value = run_program("shape main() {}").unwrap();
+        assert_eq!(
+            value,
+            "<svg width=\"100%\" height=\"100%\" xmlns=\"http://www.w3.org/2000/svg\"/>"
+        );
+
+        let value = run_program("canvas { background: \"white\" }\nshape main() {}").unwrap();
+        assert!(
+            value.contains("<rect x=\"0\" y=\"0\" width=\"100%\" height=\"100%\" fill=\"white\"/>")
+        );
+    }
+
+    #[test]
+    fn canvas_options_override_program() {
+        let line = "
+canvas { width: 100, height: 50, viewBox: [-50, -25, 100, 50], background: #000 }
+shape main() {}
+";
+        let options = Options {
+            canvas: Canvas {
+                width: Some(10.0),
+                units: Some("cm".to_owned()),
+                background: Some("red".to_owned()),
+                ..Canvas::default()
+            },
+            ..Options::default()
+        };
+        assert_eq!(
+            run_program_with_options(line, &options).unwrap(),
+            "<svg width=\"10cm\" height=\"50cm\" viewBox=\"-50 -25 100 50\" xmlns=\"http://www.w3.org/2000/svg\">\
+             <rect x=\"-50\" y=\"-25\" width=\"100\" height=\"50\" fill=\"red\"/></svg>"
+        );
+
+        // the view box comes from the program so a new size scales the drawing
+        let options = Options {
+            canvas: Canvas {
+                width: Some(1000.0),
+                height: Some(500.0),
+                ..Canvas::default()
+            },
+            ..Options::default()
+        };
+        let value = run_program_with_options(
+            "canvas { width: 100, height: 50 }\nshape main() {}",
+            &options,
+        )
+        .unwrap();
+        assert!(value.starts_with("<svg width=\"1000\" height=\"500\" viewBox=\"0 0 100 50\""));
+    }
+
+    #[test]
+    fn canvas_option_errors() {
+        let check = |canvas: Canvas| {
+            let options = Options {
+                canvas,
+                ..Options::default()
+            };
+            run_program_with_options("shape main() {}", &options).map_err(|e| e.error_type)
+        };
+
+        assert_eq!(
+            check(Canvas {
+                width: Some(-1.0),
+                ..Canvas::default()
+            }),
+            Err(InvalidCanvasSize(-1.0))
+        );
+        assert!(matches!(
+            check(Canvas {
+                height: Some(f64::NAN),
+                ..Canvas::default()
+            }),
+            Err(InvalidCanvasSize(n)) if n.is_nan()
+        ));
+        assert!(check(Canvas {
+            width: Some(0.0),
+            ..Canvas::default()
+        })
+        .is_ok());
+    }
+
+    #[test]
+    fn canvas_errors() {
+        let check = |line: &str, expected: EvalErrorType, pos: Pos| {
+            let line = format!("{}\nshape main() {{}}", line);
+            assert_eq!(
+                run_program(&line),
+                Err(EvalError::new(expected, pos)),
+                "{}",
+                line
+            );
+        };
+
+        check(
+            "canvas {}\ncanvas {}",
+            CanvasAlreadyDefined,
+            create_pos(1, 0),
+        );
+        check(
+            "canvas { width: -1 }",
+            InvalidCanvasSize(-1.0),
+            create_pos(0, 7),
+        );
+        check(
+            "canvas { height: 10% }",
+            TypeMismatch(unit_name(None), unit_name(Some(Unit::Percent))),
+            create_pos(0, 7),
+        );
+        check(
+            "canvas { units: \"em\" }",
+            InvalidCanvasUnits("em".to_owned()),
+            create_pos(0, 7),
+        );
+        check(
+            "canvas { viewBox: [0, 0, 10] }",
+            TypeMismatch("list of 4 numbers".to_owned(), "3".to_owned()),
+            create_pos(0, 7),
+        );
+        check(
+            "canvas { size: 10 }",
+            UnExpectedArg("canvas".to_owned(), "size".to_owned()),
+            create_pos(0, 7),
+        );
+    }
+
+    #[test]
+    fn seed_errors() {
+        assert_eq!(
+            run_program("seed 1\nseed 2\nshape main() {}"),
+            Err(EvalError::new(SeedAlreadyDefined, create_pos(1, 0)))
+        );
+        assert_eq!(
+            run_program("seed \"a\"\nshape main() {}").map_err(|e| e.error_type),
+            Err(TypeMismatch("number".to_owned(), "string".to_owned()))
+        );
+    }
+
+    #[test]
+    fn lists() {
+        let numbers = |ns: &[f64]| Value::List(ns.iter().map(|n| Value::from_number(*n)).collect());
+
+        check_expression("[1, 1 + 1, 3,]", numbers(&[1.0, 2.0, 3.0]));
+        check_expression("[]", numbers(&[]));
+        check_expression("[1, 2, 3][1]", Value::from_number(2.0));
+        check_expression("[1, 2, 3][-1]", Value::from_number(3.0));
+        check_expression("[[1], [2, 3]][1][0]", Value::from_number(2.0));
+        check_expression("[1, 2] == [1, 2]", Value::Bool(true));
+        check_expression(
+            "\"${[1, \"a\", [#fff]]}\"",
+            Value::from_string("[1, a, [#ffffff]]"),
+        );
+    }
+
+    #[test]
+    fn list_functions() {
+        let check = |line: &str, expected: &str| {
+            let tokens = lexer::lex(&line.to_owned()).unwrap();
+            let expr = parser::parse_expression(tokens).unwrap();
+            let output = eval_expression(&expr, &mut Context::new()).unwrap();
+
+            assert_eq!(format!("{}", output), expected, "{}", line);
+        };
+
+        check("len([1, 2, 3])", "3");
+        check("len(\"héllo\")", "5");
+        check("push([1], 2)", "[1, 2]");
+        check("concat([1], [], [2, 3])", "[1, 2, 3]");
+        check("range(3)", "[0, 1, 2]");
+        check("range(1, 3)", "[1, 2]");
+        check("range(0, 10px, 5)", "[0px, 5px]");
+        check("map([1, 4, 9], sqrt)", "[1, 2, 3]");
+        check("reverse([1, 2, 3])", "[3, 2, 1]");
+        check("sort([3, 1, 2])", "[1, 2, 3]");
+        check("sort([\"b\", \"c\", \"a\"])", "[a, b, c]");
+        check("sort([-3, 1, -2], abs)", "[1, -2, -3]");
+        check("zip([1, 2, 3], [\"a\", \"b\"])", "[[1, a], [2, b]]");
+    }
+
+    #[test]
+    fn list_errors() {
+        check_expression_error("[1, 2][2]", IndexOutOfBounds(2.0, 2), create_pos(0, 7));
+        check_expression_error("[1, 2][-3]", IndexOutOfBounds(-3.0, 2), create_pos(0, 7));
+        check_expression_error("[1, 2][0.5]", InvalidIndex(0.5), create_pos(0, 7));
+        check_expression_error(
+            "1[0]",
+            TypeMismatch("list".to_owned(), "number".to_owned()),
+            create_pos(0, 0),
+        );
+        check_expression_error(
+            "map([1], 2)",
+            TypeMismatch("function".to_owned(), "number".to_owned()),
+            create_pos(0, 9),
+        );
+        check_expression_error(
+            "filter([1], abs)",
+            TypeMismatch("bool".to_owned(), "number".to_owned()),
+            create_pos(0, 12),
+        );
+        check_expression_error(
+            "sort([1, \"a\"])",
+            TypeMismatch("string".to_owned(), "number".to_owned()),
+            create_pos(0, 5),
+        );
+        check_expression_error(
+            "map([1], rgb)",
+            NumArgs("rgb".to_owned(), 3, 1),
+            create_pos(0, 9),
+        );
+    }
+
+    #[test]
+    fn eval_program_with_lists() {
+        let line = "
+const PALETTE = [#ff69b4, #00ffff, #ffff00]
+
+fn double(n) = n * 2
+fn is_big(n) = n > 4
+
+shape main() {
+  let sizes = filter(map(range(5), double), is_big)
+  for pair in zip(sizes, PALETTE) {
+    raw_svg(value: \"${pair[0]}:${pair[1]} \")
+  }
+  let f = double
+  raw_svg(value: \"${f(len(sizes))}\")
+}
+";
+        let value = run_program(line).unwrap();
+        assert_debug_snapshot_matches!(value)
+    }
+
+    #[test]
+    fn records() {
+        let check = |line: &str, expected: &str| {
+            let tokens = lexer::lex(&line.to_owned()).unwrap();
+            let expr = parser::parse_expression(tokens).unwrap();
+            let output = eval_expression(&expr, &mut Context::new()).unwrap();
+
+            assert_eq!(format!("{}", output), expected, "{}", line);
+        };
+
+        check("{x: 1, y: 1 + 1}", "{x: 1, y: 2}");
+        check("{}", "{}");
+        check("{x: 1, y: 2}.y", "2");
+        check("{p: {x: [1, 2]}}.p.x[1]", "2");
+        check("{\"stroke-width\": 2px}", "{stroke-width: 2px}");
+        check("{x: 1, y: 2, ...{x: 3, z: 4}}", "{x: 3, y: 2, z: 4}");
+        check("{...{x: 3}, x: 1}", "{x: 1}");
+        check("{x: 1, y: 2} == {y: 2, x: 1}", "true");
+        check("{x: 1} == {x: 1, y: 2}", "false");
+        check("{a: 1turn} == {a: 360deg}", "true");
+    }
+
+    #[test]
+    fn record_errors() {
+        check_expression_error(
+            "{x: 1}.y",
+            FieldNotDefined("y".to_owned()),
+            create_pos(0, 7),
+        );
+        check_expression_error(
+            "[1].x",
+            TypeMismatch("record".to_owned(), "list".to_owned()),
+            create_pos(0, 0),
+        );
+        check_expression_error(
+            "{...[1]}",
+            TypeMismatch("record".to_owned(), "list".to_owned()),
+            create_pos(0, 4),
+        );
+    }
+
+    #[test]
+    fn eval_program_with_records() {
+        let line = "
+const STYLE = {fill: #ff69b4, stroke: \"none\"}
+
+shape dot(p, r = 1, fill = \"black\", stroke = \"black\") {
+  raw_svg(value: \"${p.x},${p.y} r=${r} ${fill} ${stroke}|\")
+}
+
+shape main() {
+  dot(p: {x: 1, y: 2})
+  dot(...STYLE, p: {x: 3, y: 4}, fill: \"red\")
+  dot(...{p: {x: 5, y: 6}, r: 2, extra: true}, ...STYLE)
+}
+";
+        let value = run_program(line).unwrap();
+        assert_debug_snapshot_matches!(value)
+    }
+
+    #[test]
+    fn vectors() {
+        let check = |line: &str, expected: &str| {
+            let tokens = lexer::lex(&line.to_owned()).unwrap();
+            let expr = parser::parse_expression(tokens).unwrap();
+            let output = eval_expression(&expr, &mut Context::new()).unwrap();
+
+            assert_eq!(format!("{}", output), expected, "{}", line);
+        };
+
+        check("(1, 2)", "1,2");
+        check("vec(1px, 2px)", "1px,2px");
+        check("(90deg, 1turn)", "90deg,360deg");
+        check("(1, 2) + (3, 4)", "4,6");
+        check("(5px, 5px) - (1px, 2px)", "4px,3px");
+        check("(1, 2) * 3px", "3px,6px");
+        check("2 * (1, 2)", "2,4");
+        check("(4, 2) / 2", "2,1");
+        check("-(1, -2)", "-1,2");
+        check("(1, 2).y", "2");
+        check("(1, 2) == vec(1, 2)", "true");
+        check("(1turn, 0) == (360deg, 0)", "true");
+        check("length((3px, 4px))", "5px");
+        check("normalize((0, 5))", "0,1");
+        check("dot((1, 2), (3px, 4px))", "11px");
+        check("round(rotate((1, 0), 90deg).y)", "1");
+        check("round(polar(2px, 0.5turn).x)", "-2px");
+    }
+
+    #[test]
+    fn vector_errors() {
+        check_expression_error(
+            "(1, 2) + 1",
+            TypeMismatch("vector".to_owned(), "number".to_owned()),
+            create_pos(0, 9),
+        );
+        check_expression_error(
+            "(1, 2) * (1, 2)",
+            TypeMismatch("number".to_owned(), "vector".to_owned()),
+            create_pos(0, 9),
+        );
+        check_expression_error(
+            "2 / (1, 2)",
+            TypeMismatch("vector".to_owned(), "number".to_owned()),
+            create_pos(0, 0),
+        );
+        check_expression_error(
+            "(1px, 2deg)",
+            TypeMismatch("number in px".to_owned(), "number in deg".to_owned()),
+            create_pos(0, 6),
+        );
+        check_expression_error(
+            "(1, 2).z",
+            FieldNotDefined("z".to_owned()),
+            create_pos(0, 7),
+        );
+        check_expression_error("normalize((0, 0))", NonFiniteResult, create_pos(0, 0));
+        check_expression_error(
+            "length(1)",
+            TypeMismatch("vector".to_owned(), "number".to_owned()),
+            create_pos(0, 7),
+        );
+    }
+
+    #[test]
+    fn eval_program_with_vectors() {
+        let line = "
+shape dot(p, r = 1, x = p.x, y = p.y) {
+  raw_svg(value: \"${x} ${y} r=${r}|\")
+}
+
+shape main() {
+  dot(p: (1, 2))
+  dot(p: (1, 2) + (3, 4), y: 0)
+  circle(center: vec(10, 10))
+  rect(position: (5, 5), size: (20, 10) * 2)
+}
+";
+        let value = run_program(line).unwrap();
+        assert_debug_snapshot_matches!(value)
+    }
+
+    #[test]
+    fn eval_program_with_elements() {
+        let line = "
+shape main() {
+  element(name: \"g\", attrs: {fill: #f00, \"stroke-width\": 2px}) {
+    for i in 0..2 {
+      element(name: \"circle\", attrs: {cx: i * 10, r: 5})
+    }
+    raw_svg(value: \"<path/>\")
+  }
+  element(...{name: \"rect\"}, attrs: {points: (1, 2)})
+}
+";
+        let value = run_program(line).unwrap();
+        assert_debug_snapshot_matches!(value)
+    }
+
+    #[test]
+    fn document_nodes_keep_their_range() {
+        let line = "shape main() {
+  element(name: \"g\") {
+    circle()
+  }
+}";
+        let tokens = lexer::lex(&line.to_owned()).unwrap();
+        let program = parser::parse_program(tokens).unwrap();
+        let svg = eval_document(&program, &Options::default()).unwrap();
+
+        let group = match &svg.children[..] {
+            [Node::Element(group)] => group,
+            nodes => panic!("expected a single group, got {:?}", nodes),
+        };
+        assert_eq!(group.name, "g");
+        assert_eq!(group.range.start, create_pos(1, 2));
+
+        match &group.children[..] {
+            [Node::Element(circle)] => {
+                assert_eq!(circle.name, "circle");
+                assert_eq!(circle.attrs[0], ("cx".to_owned(), "0".to_owned()));
+            }
+            nodes => panic!("expected a single circle, got {:?}", nodes),
+        }
+    }
+
+    #[test]
+    fn fold_only_transform_groups() {
+        let line = "shape main() {
+  translate(x: 1) {
+    rotate(angle: 90deg) {
+      circle()
+    }
+  }
+  translate(x: 2) {
+    element(name: \"g\", attrs: {transform: \"scale(2)\"}) {
+      circle()
+    }
+  }
+}";
+        let tokens = lexer::lex(&line.to_owned()).unwrap();
+        let program = parser::parse_program(tokens).unwrap();
+        let svg = eval_document(&program, &Options::default()).unwrap();
+
+        let (folded, outer) = match &svg.children[..] {
+            [Node::Element(folded), Node::Element(outer)] => (folded, outer),
+            nodes => panic!("expected two groups, got {:?}", nodes),
+        };
+
+        assert_eq!(
+            folded.attrs,
+            vec![(
+                "transform".to_owned(),
+                "translate(1 0) rotate(90)".to_owned()
+            )]
+        );
+        assert_eq!(folded.range.start, create_pos(2, 4));
+        assert!(matches!(&folded.children[..], [Node::Element(c)] if c.name == "circle"));
+
+        assert_eq!(
+            outer.attrs,
+            vec![("transform".to_owned(), "translate(2 0)".to_owned())]
+        );
+        match &outer.children[..] {
+            [Node::Element(inner)] => {
+                assert_eq!(inner.name, "g");
+                assert_eq!(inner.range.start, create_pos(7, 4));
+            }
+            nodes => panic!("expected the user group, got {:?}", nodes),
+        }
+    }
+
+    #[test]
+    fn eval_program_with_transforms() {
+        let line = "
+shape petal(angle) {
+  rotate(angle: angle, around: (50, 50)) {
+    circle(cx: 50, cy: 20)
+  }
+}
+
+shape main() {
+  translate(x: 10px, y: 20) {
+    for i in 0..3 {
+      petal(angle: i * 0.25turn)
+    }
+  }
+  translate(x: 100) {
+    scale(by: 2) {
+      skew(x: 0.5rad) {
+        rect()
+      }
+    }
+  }
+  scale(x: 50%, y: -1) {
+    circle()
+    circle()
+  }
+  skew(y: 10deg) {}
+}
+";
+        let value = run_program(line).unwrap();
+        assert_debug_snapshot_matches!(value)
+    }
+
+    #[test]
+    fn eval_program_with_groups_and_layers() {
+        let line = "
+canvas { background: #fff }
+
+layer outline {
+  group(class: \"pen\", z: -1) {
+    rect(size: (100, 100))
+  }
+}
+
+shape main() {
+  group(id: \"top\", z: 1) {
+    circle(r: 1)
+  }
+  group(opacity: 50%) {
+    circle(r: 2)
+    group(z: -1) {
+      circle(r: 3)
+    }
+  }
+  group(id: \"back\", opacity: 0.25, z: -2) {}
+}
+
+layer labels {
+  circle(r: 4)
+}
+";
+        let value = run_program(line).unwrap();
+        assert_debug_snapshot_matches!(value)
+    }
+
+    #[test]
+    fn group_errors() {
+        let check = |line: &str, expected: EvalErrorType, pos: Pos| {
+            assert_eq!(
+                run_program(line),
+                Err(EvalError::new(expected, pos)),
+                "{}",
+                line
+            );
+        };
+
+        check(
+            "shape group() {}\nshape main() {}",
+            ShapeAlreadyDefined("group".to_owned()),
+            create_pos(0, 0),
+        );
+        check(
+            "shape main() {}\nshape translate(x) {}",
+            ShapeAlreadyDefined("translate".to_owned()),
+            create_pos(1, 0),
+        );
+        check(
+            "shape main() {\ngroup(z: 1)\n}",
+            MissingChildren("group".to_owned()),
+            create_pos(1, 0),
+        );
+        check(
+            "shape main() {\ngroup(z: 1px) {}\n}",
+            TypeMismatch(unit_name(None), unit_name(Some(Unit::Px))),
+            create_pos(1, 0),
+        );
+        check(
+            "shape main() {\ngroup(opacity: 1deg) {}\n}",
+            TypeMismatch(
+                "number or percentage".to_owned(),
+                unit_name(Some(Unit::Deg)),
+            ),
+            create_pos(1, 0),
+        );
+        check(
+            "shape main() {\ngroup(fill: #fff) {}\n}",
+            UnExpectedArg("group".to_owned(), "fill".to_owned()),
+            create_pos(1, 0),
+        );
+        check(
+            "shape main() {}\nlayer a {}\nlayer a {}",
+            LayerAlreadyDefined("a".to_owned()),
+            create_pos(2, 0),
+        );
+    }
+
+    #[test]
+    fn transform_errors() {
+        let check = |body: &str, expected: EvalErrorType| {
+            let line = format!("shape main() {{\n{}\n}}", body);
+            assert_eq!(
+                run_program(&line),
+                Err(EvalError::new(expected, create_pos(1, 0))),
+                "{}",
+                body
+            );
+        };
+
+        check(
+            "rotate() { circle() }",
+            MissingRequiredArg("rotate".to_owned(), "angle".to_owned()),
+        );
+        check("translate(x: 1)", MissingChildren("translate".to_owned()));
+        check(
+            "translate(x: 10%) {}",
+            TypeMismatch(unit_name(None), unit_name(Some(Unit::Percent))),
+        );
+        check(
+            "rotate(angle: 10px) {}",
+            TypeMismatch("angle".to_owned(), unit_name(Some(Unit::Px))),
+        );
+        check(
+            "rotate(angle: pi / 2) {}",
+            TypeMismatch("angle".to_owned(), unit_name(None)),
+        );
+        check(
+            "rotate(angle: 10deg, around: 5) {}",
+            TypeMismatch("vector".to_owned(), "number".to_owned()),
+        );
+        check(
+            "scale(z: 2) {}",
+            UnExpectedArg("scale".to_owned(), "z".to_owned()),
+        );
+        check(
+            "skew() {}",
+            MissingRequiredArg("skew".to_owned(), "x".to_owned()),
+        );
+    }
+
+    #[test]
+    fn stdlib_circle() {
+        let line = "shape main() {\n  circle()\n  circle(center: (50, 50), r: 20, fill: #00f, stroke: \"black\", strokeWidth: 2)\n}";
+        let value = run_program(line).unwrap();
+        assert_debug_snapshot_matches!(value)
+    }
+
+    #[test]
+    fn stdlib_ellipse() {
+        let line = "shape main() {\n  ellipse()\n  ellipse(center: (50, 25), radius: (40, 20), fill: \"none\", stroke: #000)\n}";
+        let value = run_program(line).unwrap();
+        assert_debug_snapshot_matches!(value)
+    }
+
+    #[test]
+    fn stdlib_rect() {
+        let line = "shape main() {\n  rect()\n  rect(position: (10, 10), size: (80, 40), rx: 5)\n  rect(width: 20, height: 20, rx: 4, ry: 2)\n}";
+        let value = run_program(line).unwrap();
+        assert_debug_snapshot_matches!(value)
+    }
+
+    #[test]
+    fn stdlib_line() {
+        let line = "shape main() {\n  line()\n  line(from: (0, 50), to: (100, 50), stroke: \"red\", strokeWidth: 3)\n}";
+        let value = run_program(line).unwrap();
+        assert_debug_snapshot_matches!(value)
+    }
+
+    #[test]
+    fn stdlib_polyline() {
+        let line = "fn zigzag(i) = (i * 10, i % 2 * 10)\n\nshape main() {\n  polyline(points: [(0, 0), (10, 20), (20, 0)])\n  polyline(points: map(0..4, zigzag), stroke: #f00)\n}";
+        let value = run_program(line).unwrap();
+        assert_debug_snapshot_matches!(value)
+    }
+
+    #[test]
+    fn stdlib_polygon() {
+        let line = "shape main() {\n  polygon(points: [(50, 0), (100, 100), (0, 100)])\n  polygon(points: [(0, 0), (10, 0), (5, 10)], fill: \"none\", stroke: \"black\", strokeWidth: 1)\n}";
+        let value = run_program(line).unwrap();
+        assert_debug_snapshot_matches!(value)
+    }
+
+    #[test]
+    fn stdlib_text() {
+        let line = "shape main() {\n  text(value: \"Hello\")\n  text(value: \"a < b\", position: (50, 50), fontSize: 24, fontWeight: \"bold\", textAnchor: \"middle\")\n}";
+        let value = run_program(line).unwrap();
+        assert_debug_snapshot_matches!(value)
+    }
+
+    #[test]
+    fn stdlib_image() {
+        let line = "shape main() {\n  image(href: \"cat.png\")\n  image(href: \"cat.png\", position: (10, 10), size: (50, 30), preserveAspectRatio: \"none\")\n}";
+        let value = run_program(line).unwrap();
+        assert_debug_snapshot_matches!(value)
+    }
+
+    #[test]
+    fn stdlib_shapes_take_style_args() {
+        let shapes = load_stdlib_shapes().unwrap();
+        assert_eq!(shapes.len(), 8);
+
+        for shape in shapes.values() {
+            for name in &["fill", "strokeWidth", "opacity", "transform", "class"] {
+                assert!(
+                    shape.args.iter().any(|arg| arg.name == *name),
+                    "{} is missing {}",
+                    shape.name,
+                    name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn stdlib_common_style_args() {
+        let line = "shape main() {\n  circle(opacity: 0.5, fillOpacity: 50%, id: \"dot\", class: \"a b\")\n  line(strokeDasharray: [4, 2], strokeLinecap: \"round\", strokeLinejoin: \"bevel\", transform: \"rotate(45)\")\n}";
+        let value = run_program(line).unwrap();
+        assert_debug_snapshot_matches!(value)
+    }
+
+    #[test]
+    fn element_errors() {
+        let check = |body: &str, expected: EvalErrorType, pos: Pos| {
+            let line = format!("shape main() {{\n{}\n}}", body);
+            assert_eq!(
+                run_program(&line),
+                Err(EvalError::new(expected, pos)),
+                "{}",
+                body
+            );
+        };
+
+        check(
+            "element(attrs: {})",
+            MissingRequiredArg("element".to_owned(), "name".to_owned()),
+            create_pos(1, 0),
+        );
+        check(
+            "element(name: \"g\", fill: 1)",
+            UnExpectedArg("element".to_owned(), "fill".to_owned()),
+            create_pos(1, 0),
+        );
+        check(
+            "element(name: \"g\", attrs: {d: {x: 1}})",
+            TypeMismatch("attribute value".to_owned(), "record".to_owned()),
+            create_pos(1, 0),
+        );
+        check(
+            "circle() { circle() }",
+            UnExpectedChildren("circle".to_owned()),
+            create_pos(1, 0),
+        );
+        check(
+            "element(name: \"script\")",
+            UnknownElement("script".to_owned()),
+            create_pos(1, 0),
+        );
+        check(
+            "element(name: \"g\", attrs: {onload: \"alert(1)\"})",
+            UnknownAttribute("g".to_owned(), "onload".to_owned()),
+            create_pos(1, 0),
+        );
+        check(
+            "element(name: \"a\", attrs: {href: \"javascript:alert(1)\"})",
+            UnsafeAttrValue("href".to_owned()),
+            create_pos(1, 0),
+        );
+        check(
+            "element(name: \"animate\", attrs: {attributeName: \"fill\", values: \"#a;javascript:alert(1)\"})",
+            UnsafeAttrValue("values".to_owned()),
+            create_pos(1, 0),
+        );
+        check(
+            "element(name: \"set\", attrs: {attributeName: \"href\", to: \"#b\"})",
+            UnsafeAttrValue("attributeName".to_owned()),
+            create_pos(1, 0),
+        );
+    }
+
+    #[test]
+    fn values_are_escaped() {
+        let line = "
+shape main() {
+  circle(fill: \"red\\\" onload=\\\"alert(1)\")
+  element(name: \"text\", text: \"<script>alert(1)</script>\")
+}
+";
+        let value = run_program_with_options(line, &Options::default()).unwrap();
+        assert!(value.contains("fill=\"red&quot; onload=&quot;alert(1)\""));
+        assert!(value.contains("<text>&lt;script&gt;alert(1)&lt;/script&gt;</text>"));
+    }
+
+    #[test]
+    fn raw_svg_is_disabled_by_default() {
+        let line = "
+shape main() {
+  raw_svg(value: \"<script/>\")
+}
+";
+        assert_eq!(
+            run_program_with_options(line, &Options::default()),
+            Err(EvalError::new(RawSvgDisabled, create_pos(1, 2)))
+        );
+        assert!(run_program(line).unwrap().contains("<script/>"));
+    }
+
+    #[test]
+    fn native_consts_can_be_shadowed() {
+        let line = "
+const pi = 3
+
+shape main() {
+  raw_svg(value: \"${pi}\")
+}
+";
+        let value = run_program(line).unwrap();
+        assert!(value.contains(">3</svg>"));
+    }
+
+    #[test]
+    fn string_interpolation() {
+        check_expression(
+            "\"r=${10px * 2} fill=${#f00}${\"!\"}\"",
+            Value::from_string("r=20px fill=#ff0000!"),
+        );
+    }
+
+    #[test]
+    fn interpolation_error_position() {
+        let line = "
+shape main() {
+  raw_svg(value: \"<g>${missing}</g>\")
+}
+";
+        let e = run_program(line).unwrap_err();
+        assert_eq!(e.pos, create_pos(1, 23));
+    }
+
+    #[test]
+    fn logical_ops() {
+        check_expression("true", Value::Bool(true));
+        check_expression("!true", Value::Bool(false));
+        check_expression("1 < 2 && 2 <= 2", Value::Bool(true));
+        check_expression("3 > 4 || 4 >= 5", Value::Bool(false));
+        check_expression("1 + 1 == 2", Value::Bool(true));
+        check_expression("\"a\" != \"b\"", Value::Bool(true));
+        check_expression("\"a\" < \"b\"", Value::Bool(true));
+        check_expression("180deg == 0.5turn", Value::Bool(true));
+        check_expression("#fff == #ffffff", Value::Bool(true));
+        check_expression("1 == \"1\"", Value::Bool(false));
+        check_expression("false && missing", Value::Bool(false));
+        check_expression("true || missing", Value::Bool(true));
+    }
+
+    #[test]
+    fn logical_op_errors() {
+        check_expression_error(
+            "1 && true",
+            TypeMismatch("bool".to_owned(), "number".to_owned()),
+            create_pos(0, 0),
+        );
+        check_expression_error(
+            "!1",
+            TypeMismatch("bool".to_owned(), "number".to_owned()),
+            create_pos(0, 1),
+        );
+        check_expression_error(
+            "1 < \"a\"",
+            TypeMismatch("number".to_owned(), "string".to_owned()),
+            create_pos(0, 4),
+        );
+        check_expression_error(
+            "true < false",
+            TypeMismatch("number or string".to_owned(), "bool".to_owned()),
+            create_pos(0, 0),
+        );
+        check_expression_error(
+            "1px == 1%",
+            TypeMismatch("number in px".to_owned(), "number in %".to_owned()),
+            create_pos(0, 7),
+        );
+    }
+
+    #[test]
+    fn arithmetic_ops() {
+        check_expression("7 % 3", Value::from_number(1.0));
+        check_expression("10%-3", Value::from_number(-2.0));
+        check_expression("-1 % 4", Value::from_number(3.0));
+        check_expression("370deg % 1turn", Value::Number(10.0, Some(Unit::Deg)));
+        check_expression("7 ~/ 2", Value::from_number(3.0));
+        check_expression("-7 ~/ 2", Value::from_number(-4.0));
+        check_expression("25px ~/ 10", Value::Number(2.0, Some(Unit::Px)));
+        check_expression("2 ** 3 ** 2", Value::from_number(512.0));
+        check_expression("2 * 3 ** 2", Value::from_number(18.0));
+        check_expression("-2 ** 2", Value::from_number(-4.0));
+        check_expression("2 ** -1", Value::from_number(0.5));
+        check_expression("-2 * 3", Value::from_number(-6.0));
+    }
+
+    #[test]
+    fn arithmetic_errors() {
+        check_expression_error("5 % 0", ModuloByZero, create_pos(0, 4));
+        check_expression_error("1 / 0", NonFiniteResult, create_pos(0, 2));
+        check_expression_error("1 ~/ 0", NonFiniteResult, create_pos(0, 2));
+        check_expression_error("10 ** 400", NonFiniteResult, create_pos(0, 3));
+        check_expression_error(
+            "2px ** 2",
+            TypeMismatch("number".to_owned(), "number in px".to_owned()),
+            create_pos(0, 0),
+        );
+    }
+
+    #[test]
+    fn conditional_expressions() {
+        check_expression("true ? 1 : 2", Value::from_number(1.0));
+        check_expression("1 > 2 ? 1 : 2 < 3 ? 2 : 3", Value::from_number(2.0));
+        check_expression("false ? missing : \"none\"", Value::from_string("none"));
+    }
+
+    #[test]
+    fn conditional_expects_bool() {
+        check_expression_error(
+            "1 ? 2 : 3",
+            TypeMismatch("bool".to_owned(), "number".to_owned()),
+            create_pos(0, 0),
+        );
+    }
+
+    #[test]
+    fn eval_program_with_if() {
+        let line = "
+shape badge(outlined = false, big = false) {
+  if outlined {
+    raw_svg(value: \"outlined \")
+  } else if big {
+    raw_svg(value: \"big \")
+  } else {
+    raw_svg(value: \"filled \")
+  }
+  raw_svg(value: big ? \"!\" : \".\")
+}
+
+shape main() {
+  badge(outlined: true)
+  badge(big: true)
+  badge()
+}
+";
+        let value = run_program(line).unwrap();
+        assert_debug_snapshot_matches!(value)
+    }
+
+    #[test]
+    fn ranges() {
+        let numbers = |ns: &[f64]| Value::List(ns.iter().map(|n| Value::from_number(*n)).collect());
+
+        check_expression("0..4", numbers(&[0.0, 1.0, 2.0, 3.0]));
+        check_expression("0..5 step 2", numbers(&[0.0, 2.0, 4.0]));
+        check_expression("3..0 step -1", numbers(&[3.0, 2.0, 1.0]));
+        check_expression("0..1 step 0.5", numbers(&[0.0, 0.5]));
+        check_expression("4..0", numbers(&[]));
+        check_expression(
+            "0..1turn step 120deg",
+            Value::List(vec![
+                Value::Number(0.0, Some(Unit::Turn)),
+                Value::Number(1.0 / 3.0, Some(Unit::Turn)),
+                Value::Number(2.0 / 3.0, Some(Unit::Turn)),
+            ]),
+        );
+    }
+
+    #[test]
+    fn range_errors() {
+        check_expression_error("0..10 step 0", RangeStepZero, create_pos(0, 11));
+        check_expression_error("0..1e9", RangeTooLarge(RANGE_LIMIT), create_pos(0, 0));
+        check_expression_error(
+            "0px..10%",
+            TypeMismatch("number in px".to_owned(), "number in %".to_owned()),
+            create_pos(0, 5),
+        );
+    }
+
+    #[test]
+    fn eval_program_with_for() {
+        let line = "
+shape dots(n) {
+  for i in 0..n {
+    for j in 0..i + 1 {
+      raw_svg(value: \"${i}${j} \")
+    }
+  }
+}
+
+shape main() {
+  dots(n: 3)
+}
+";
+        let value = run_program(line).unwrap();
+        assert_debug_snapshot_matches!(value)
+    }
+
+    #[test]
+    fn for_loop_scope() {
+        let line = "
+shape main() {
+  for i in 0..2 {}
+  raw_svg(value: i)
+}
+";
+        assert_eq!(
+            run_program(line).map_err(|e| e.error_type),
+            Err(VariableNotDefined("i".to_owned()))
+        );
+    }
+
+    #[test]
+    fn eval_program_with_let() {
+        let line = "
+shape badge(size) {
+  let r = size / 2
+  let label = \"r=${r}\"
+  if r > 2 {
+    let r = r * 10
+    raw_svg(value: \"${label} inner=${r} \")
+  }
+  let r = r + 1
+  raw_svg(value: \"outer=${r}\")
+}
+
+shape main() {
+  badge(size: 10)
+}
+";
+        let value = run_program(line).unwrap();
+        assert_debug_snapshot_matches!(value)
+    }
+
+    #[test]
+    fn let_scope() {
+        let line = "
+shape main() {
+  if true {
+    let x = 1
+  }
+  raw_svg(value: x)
+}
+";
+        assert_eq!(
+            run_program(line).map_err(|e| e.error_type),
+            Err(VariableNotDefined("x".to_owned()))
+        );
+    }
+
+    #[test]
+    fn eval_program_with_consts() {
+        let line = "
+const SIZE = 10
+const HALF = SIZE / 2
+
+shape dot(r = HALF) {
+  raw_svg(value: \"${r}/${SIZE} \")
+}
+
+shape main() {
+  dot()
+  dot(r: SIZE)
+}
+";
+        let value = run_program(line).unwrap();
+        assert_debug_snapshot_matches!(value)
+    }
+
+    #[test]
+    fn const_errors() {
+        let check = |line: &str, expected: EvalErrorType, pos: Pos| {
+            let program = format!("{}\nshape main() {{}}", line);
+            assert_eq!(
+                run_program(&program),
+                Err(EvalError::new(expected, pos)),
+                "{}",
+                line
+            );
+        };
+
+        check(
+            "const A = B + 1\nconst B = 1",
+            ConstUsedBeforeDefinition("B".to_owned()),
+            create_pos(0, 10),
+        );
+        check(
+            "const A = A",
+            CyclicConst("A".to_owned()),
+            create_pos(0, 10),
+        );
+        check(
+            "const A = B\nconst B = A",
+            CyclicConst("A".to_owned()),
+            create_pos(1, 10),
+        );
+        check(
+            "const A = B + 1\nconst B = C * 2\nconst C = A",
+            CyclicConst("A".to_owned()),
+            create_pos(2, 10),
+        );
+        check(
+            "fn f() = B\nconst A = f()\nconst B = A",
+            CyclicConst("A".to_owned()),
+            create_pos(2, 10),
+        );
+        check(
+            "const A = 1\nconst A = 2",
+            ConstAlreadyDefined("A".to_owned()),
+            create_pos(1, 0),
+        );
+        check(
+            "const A = nope",
+            VariableNotDefined("nope".to_owned()),
+            create_pos(0, 10),
+        );
+    }
+
+    #[test]
+    fn eval_program_with_functions() {
+        let line = "
+const SCALE = 2
+
+fn double(n) = n * SCALE
+fn fact(n) = n <= 1 ? 1 : n * fact(n - 1)
+fn label(n, prefix = \"n=\") = \"${prefix}${n}\"
+
+shape main() {
+  raw_svg(value: label(double(fact(4))) + \" \" + label(1, \"m=\"))
+}
+";
+        let value = run_program(line).unwrap();
+        assert_debug_snapshot_matches!(value)
+    }
+
+    #[test]
+    fn function_errors() {
+        let check = |line: &str, expected: EvalErrorType| {
+            assert_eq!(
+                run_program(line).map_err(|e| e.error_type),
+                Err(expected),
+                "{}",
+                line
+            );
+        };
+
+        check(
+            "fn f(a, b = 1) = a\nshape main() { raw_svg(value: f()) }",
+            NumArgs("f".to_owned(), 1, 0),
+        );
+        check(
+            "fn f(a) = a\nshape main() { raw_svg(value: f(1, 2)) }",
+            NumArgs("f".to_owned(), 1, 2),
+        );
+        check(
+            "fn f(a) = a\nfn f(b) = b\nshape main() {}",
+            FunctionAlreadyDefined("f".to_owned()),
+        );
+        check(
+            "fn rotate(a) = a\nshape main() {}",
+            FunctionAlreadyDefined("rotate".to_owned()),
+        );
+        check(
+            "fn f() = 1\nshape main() { f() }",
+            FunctionUsedAsShape("f".to_owned()),
+        );
+        check(
+            "const C = 1\nfn f(a) = a + c\nshape main() { raw_svg(value: f(1)) }",
+            VariableNotDefined("c".to_owned()),
+        );
+        match run_program("fn f(n) = f(n)\nshape main() { raw_svg(value: f(1)) }") {
+            Err(EvalError {
+                error_type: StackOverflow(_),
+                ..
+            }) => (),
+            result => panic!("expected stack overflow, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn simple_find_shapes() {
+        let line = "
+shape main() {}
+shape circle(r) {}
+shape rect(w, h) {}
+";
+        let tokens = lexer::lex(&line.to_owned()).unwrap();
+        let program = parser::parse_program(tokens).unwrap();
+
+        let shapes = find_shapes(HashMap::new(), &program, true).unwrap();
+
+        assert_eq!(shapes.len(), 3)
+    }
+
+    #[test]
     fn shape_already_defined() {
         let line = "
 shape main() {}
@@ -468,7 +3219,7 @@ shape circle(r) {}
     fn eval_simple_program() {
         let line = "
 shape main() {
-  svg(value: \"hello\")
+  raw_svg(value: \"hello\")
 }
 ";
         let value = run_program(line).unwrap();
@@ -479,7 +3230,7 @@ shape main() {
     fn eval_program_with_call() {
         let line = "
 shape test1(c) {
-  svg(value: c)
+  raw_svg(value: c)
 }
 
 shape test2(c) {
@@ -498,7 +3249,7 @@ shape main() {
     fn eval_program_with_default_arg() {
         let line = "
 shape test(c = \"hello\") {
-  svg(value: c)
+  raw_svg(value: c)
 }
 
 shape main() {
@@ -509,11 +3260,22 @@ shape main() {
         assert_debug_snapshot_matches!(value)
     }
 
+    #[test]
+    fn eval_program_with_units() {
+        let line = "
+shape main() {
+  circle(cx: 50%, cy: 25%, r: 10px)
+}
+";
+        let value = run_program(line).unwrap();
+        assert_debug_snapshot_matches!(value)
+    }
+
     #[test]
     fn shape_scope1() {
         let line = "
 shape test1(r) {
-  svg(value: c)
+  raw_svg(value: c)
 }
 
 shape test2(c) {
@@ -0,0 +1,118 @@
+use std::cell::Cell;
+
+/// A seeded source of random numbers and Perlin noise. The same seed always
+/// produces the same sequence, so programs render identically every time.
+pub struct Random {
+    state: Cell<u64>,
+    perm: Vec<usize>,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        let random = Random {
+            state: Cell::new(seed),
+            perm: vec![],
+        };
+
+        // shuffle the noise permutation table with the same seed
+        let mut perm: Vec<usize> = (0..256).collect();
+        for i in (1..perm.len()).rev() {
+            let j = random.below(i + 1);
+            perm.swap(i, j);
+        }
+
+        Random {
+            state: Cell::new(seed),
+            perm: perm.iter().chain(perm.iter()).cloned().collect(),
+        }
+    }
+
+    // splitmix64
+    fn next_u64(&self) -> u64 {
+        let state = self.state.get().wrapping_add(0x9e37_79b9_7f4a_7c15);
+        self.state.set(state);
+
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number from 0 up to but not including 1.
+    pub fn next_f64(&self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// An index from 0 up to but not including `n`.
+    pub fn below(&self, n: usize) -> usize {
+        (self.next_f64() * n as f64) as usize
+    }
+
+    /// Improved Perlin noise, between -1 and 1.
+    pub fn noise(&self, x: f64, y: f64, z: f64) -> f64 {
+        let p = &self.perm;
+
+        let (xi, yi, zi) = (wrap(x), wrap(y), wrap(z));
+        let (x, y, z) = (x - x.floor(), y - y.floor(), z - z.floor());
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let a = p[xi] + yi;
+        let aa = p[a] + zi;
+        let ab = p[a + 1] + zi;
+        let b = p[xi + 1] + yi;
+        let ba = p[b] + zi;
+        let bb = p[b + 1] + zi;
+
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, grad(p[aa], x, y, z), grad(p[ba], x - 1.0, y, z)),
+                lerp(
+                    u,
+                    grad(p[ab], x, y - 1.0, z),
+                    grad(p[bb], x - 1.0, y - 1.0, z),
+                ),
+            ),
+            lerp(
+                v,
+                lerp(
+                    u,
+                    grad(p[aa + 1], x, y, z - 1.0),
+                    grad(p[ba + 1], x - 1.0, y, z - 1.0),
+                ),
+                lerp(
+                    u,
+                    grad(p[ab + 1], x, y - 1.0, z - 1.0),
+                    grad(p[bb + 1], x - 1.0, y - 1.0, z - 1.0),
+                ),
+            ),
+        )
+    }
+}
+
+fn wrap(n: f64) -> usize {
+    (n.floor() as i64 & 255) as usize
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+fn grad(hash: usize, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
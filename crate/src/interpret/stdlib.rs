@@ -1,41 +1,185 @@
+use crate::parser::ast::*;
+
+// The style args every shape takes after its own, with the attributes they
+// set. Shapes only declare the ones they give a default other than `false`,
+// like `fill`. Args that are `false` are left out of the output so the SVG
+// defaults apply.
+static STYLE_ARGS: &[(&str, &str)] = &[
+    ("fill", "fill"),
+    ("stroke", "stroke"),
+    ("strokeWidth", "stroke-width"),
+    ("opacity", "opacity"),
+    ("fillOpacity", "fill-opacity"),
+    ("strokeDasharray", "stroke-dasharray"),
+    ("strokeLinecap", "stroke-linecap"),
+    ("strokeLinejoin", "stroke-linejoin"),
+    ("transform", "transform"),
+    ("id", "id"),
+    ("class", "class"),
+];
+
+/// Adds the style args a stdlib shape does not declare to its args, and sets
+/// their attributes in the `attrs` record of its `element` call.
+pub fn add_style_args(shape: &mut Shape) {
+    let range = shape.block.range;
+
+    let attrs = shape.block.stmts.iter_mut().find_map(|stmt| match stmt {
+        Stmt::Call(call) if call.ident == "element" => {
+            call.args.iter_mut().find(|arg| arg.name == "attrs")
+        }
+        _ => None,
+    });
+    let entries = match attrs {
+        Some(NamedArg {
+            expr: Expr::Record(entries, _),
+            ..
+        }) => entries,
+        _ => return,
+    };
+
+    for (arg, attr) in STYLE_ARGS.iter() {
+        if !shape.args.iter().any(|a| a.name == *arg) {
+            shape.args.push(Arg {
+                name: arg.to_string(),
+                default: Some(Expr::Literal(Literal::Bool(false), range)),
+            });
+        }
+
+        let is_set = entries
+            .iter()
+            .any(|entry| matches!(entry, RecordEntry::Field(name, _) if name == attr));
+        if !is_set {
+            entries.push(RecordEntry::Field(
+                attr.to_string(),
+                Expr::Name(arg.to_string(), range),
+            ));
+        }
+    }
+}
+
 pub fn get_stdlib() -> String {
     r#"
 shape circle(
-  cx=0,
-  cy=0,
+  center=(0, 0),
+  cx=center.x,
+  cy=center.y,
   r=10,
   fill="hotpink",
   stroke="none",
   strokeWidth=0) {
 
-  svg(value: "<circle
-    cx=\"" + cx + "\"
-    cy=\"" + cy + "\"
-    r=\"" + r + "\"
-    fill=\"" + fill + "\"
-    stroke=\"" + stroke + "\"
-    strokeWidth=\"" + strokeWidth + "\"
-  />")
+  element(name: "circle", attrs: {cx: cx, cy: cy, r: r})
+}
+
+shape ellipse(
+  center=(0, 0),
+  cx=center.x,
+  cy=center.y,
+  radius=(20, 10),
+  rx=radius.x,
+  ry=radius.y,
+  fill="hotpink",
+  stroke="none",
+  strokeWidth=0) {
+
+  element(name: "ellipse", attrs: {cx: cx, cy: cy, rx: rx, ry: ry})
 }
 
 shape rect(
-  x=0,
-  y=0,
-  width=10,
-  height=10,
+  position=(0, 0),
+  x=position.x,
+  y=position.y,
+  size=(10, 10),
+  width=size.x,
+  height=size.y,
+  rx=false,
+  ry=false,
   fill="hotpink",
   stroke="none",
   strokeWidth=0) {
 
-  svg(value: "<rect
-    x=\"" + x + "\"
-    y=\"" + y + "\"
-    width=\"" + width + "\"
-    height=\"" + height + "\"
-    fill=\"" + fill + "\"
-    stroke=\"" + stroke + "\"
-    strokeWidth=\"" + strokeWidth + "\"
-  />")
+  element(name: "rect", attrs: {
+    x: x,
+    y: y,
+    width: width,
+    height: height,
+    rx: rx,
+    ry: ry,
+  })
+}
+
+shape line(
+  from=(0, 0),
+  to=(10, 10),
+  x1=from.x,
+  y1=from.y,
+  x2=to.x,
+  y2=to.y,
+  stroke="black",
+  strokeWidth=1) {
+
+  element(name: "line", attrs: {x1: x1, y1: y1, x2: x2, y2: y2})
+}
+
+shape polyline(
+  points,
+  fill="none",
+  stroke="black",
+  strokeWidth=1) {
+
+  element(name: "polyline", attrs: {points: points})
+}
+
+shape polygon(
+  points,
+  fill="hotpink",
+  stroke="none",
+  strokeWidth=0) {
+
+  element(name: "polygon", attrs: {points: points})
+}
+
+shape text(
+  value,
+  position=(0, 0),
+  x=position.x,
+  y=position.y,
+  fontSize=16,
+  fontFamily="sans-serif",
+  fontWeight=false,
+  textAnchor=false,
+  fill="black",
+  stroke="none",
+  strokeWidth=0) {
+
+  element(name: "text", text: value, attrs: {
+    x: x,
+    y: y,
+    "font-size": fontSize,
+    "font-family": fontFamily,
+    "font-weight": fontWeight,
+    "text-anchor": textAnchor,
+  })
+}
+
+shape image(
+  href,
+  position=(0, 0),
+  x=position.x,
+  y=position.y,
+  size=(100, 100),
+  width=size.x,
+  height=size.y,
+  preserveAspectRatio=false) {
+
+  element(name: "image", attrs: {
+    href: href,
+    x: x,
+    y: y,
+    width: width,
+    height: height,
+    preserveAspectRatio: preserveAspectRatio,
+  })
 }
 "#
     .to_owned()
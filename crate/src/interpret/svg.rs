@@ -0,0 +1,496 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::utils::*;
+
+/// The SVG document built by evaluating a program. Every node keeps the
+/// source range of the call that created it.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Node {
+    Element(Element),
+    Text(String, Range),
+    // markup from `raw_svg(value: ...)` which is written out as is
+    Raw(String, Range),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Element {
+    pub name: String,
+    // attributes in the order they were given
+    pub attrs: Vec<(String, String)>,
+    pub children: Vec<Node>,
+    // siblings are written out from the lowest z to the highest, keeping
+    // source order for equal values
+    pub z: f64,
+    pub range: Range,
+}
+
+impl Node {
+    pub fn z(&self) -> f64 {
+        match self {
+            Node::Element(element) => element.z,
+            _ => 0.0,
+        }
+    }
+}
+
+impl Element {
+    pub fn new(name: &str, range: Range) -> Element {
+        Element {
+            name: name.to_owned(),
+            attrs: vec![],
+            children: vec![],
+            z: 0.0,
+            range,
+        }
+    }
+
+    /// Set an attribute, replacing an earlier value with the same name.
+    pub fn set_attr(&mut self, name: &str, value: String) {
+        match self.attrs.iter_mut().find(|(key, _)| key == name) {
+            Some(attr) => attr.1 = value,
+            None => self.attrs.push((name.to_owned(), value)),
+        }
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Node::Element(element) => write!(f, "{}", element),
+            Node::Text(text, _) => write!(f, "{}", escape(text, false)),
+            Node::Raw(markup, _) => write!(f, "{}", markup),
+        }
+    }
+}
+
+impl fmt::Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<{}", self.name)?;
+        for (name, value) in self.attrs.iter() {
+            write!(f, " {}=\"{}\"", name, escape(value, true))?;
+        }
+
+        if self.children.is_empty() {
+            return write!(f, "/>");
+        }
+
+        let mut children: Vec<&Node> = self.children.iter().collect();
+        children.sort_by(|a, b| a.z().partial_cmp(&b.z()).unwrap_or(Ordering::Equal));
+
+        write!(f, ">")?;
+        for child in children {
+            write!(f, "{}", child)?;
+        }
+        write!(f, "</{}>", self.name)
+    }
+}
+
+fn escape(value: &str, in_attr: bool) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' if in_attr => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// Elements from the SVG 1.1 and SVG 2 specs, leaving out `script` and
+// `foreignObject` which can run code or embed HTML.
+static ELEMENTS: &[&str] = &[
+    "a",
+    "animate",
+    "animateMotion",
+    "animateTransform",
+    "circle",
+    "clipPath",
+    "defs",
+    "desc",
+    "ellipse",
+    "feBlend",
+    "feColorMatrix",
+    "feComponentTransfer",
+    "feComposite",
+    "feConvolveMatrix",
+    "feDiffuseLighting",
+    "feDisplacementMap",
+    "feDistantLight",
+    "feDropShadow",
+    "feFlood",
+    "feFuncA",
+    "feFuncB",
+    "feFuncG",
+    "feFuncR",
+    "feGaussianBlur",
+    "feImage",
+    "feMerge",
+    "feMergeNode",
+    "feMorphology",
+    "feOffset",
+    "fePointLight",
+    "feSpecularLighting",
+    "feSpotLight",
+    "feTile",
+    "feTurbulence",
+    "filter",
+    "g",
+    "image",
+    "line",
+    "linearGradient",
+    "marker",
+    "mask",
+    "metadata",
+    "mpath",
+    "path",
+    "pattern",
+    "polygon",
+    "polyline",
+    "radialGradient",
+    "rect",
+    "set",
+    "stop",
+    "svg",
+    "switch",
+    "symbol",
+    "text",
+    "textPath",
+    "title",
+    "tspan",
+    "use",
+    "view",
+];
+
+// Core, presentation and element specific attributes. Event handlers such as
+// `onload` are deliberately missing.
+static ATTRIBUTES: &[&str] = &[
+    "accumulate",
+    "additive",
+    "alignment-baseline",
+    "amplitude",
+    "attributeName",
+    "attributeType",
+    "azimuth",
+    "baseFrequency",
+    "baseline-shift",
+    "begin",
+    "bias",
+    "by",
+    "calcMode",
+    "class",
+    "clip",
+    "clip-path",
+    "clip-rule",
+    "clipPathUnits",
+    "color",
+    "color-interpolation",
+    "color-interpolation-filters",
+    "color-rendering",
+    "crossorigin",
+    "cursor",
+    "cx",
+    "cy",
+    "d",
+    "decoding",
+    "diffuseConstant",
+    "direction",
+    "display",
+    "divisor",
+    "dominant-baseline",
+    "download",
+    "dur",
+    "dx",
+    "dy",
+    "edgeMode",
+    "elevation",
+    "end",
+    "exponent",
+    "fill",
+    "fill-opacity",
+    "fill-rule",
+    "filter",
+    "filterUnits",
+    "flood-color",
+    "flood-opacity",
+    "font-family",
+    "font-size",
+    "font-size-adjust",
+    "font-stretch",
+    "font-style",
+    "font-variant",
+    "font-weight",
+    "fr",
+    "from",
+    "fx",
+    "fy",
+    "gradientTransform",
+    "gradientUnits",
+    "height",
+    "href",
+    "hreflang",
+    "id",
+    "image-rendering",
+    "in",
+    "in2",
+    "intercept",
+    "k1",
+    "k2",
+    "k3",
+    "k4",
+    "kernelMatrix",
+    "kernelUnitLength",
+    "keyPoints",
+    "keySplines",
+    "keyTimes",
+    "lang",
+    "lengthAdjust",
+    "letter-spacing",
+    "lighting-color",
+    "limitingConeAngle",
+    "marker-end",
+    "marker-mid",
+    "marker-start",
+    "markerHeight",
+    "markerUnits",
+    "markerWidth",
+    "mask",
+    "maskContentUnits",
+    "maskUnits",
+    "max",
+    "method",
+    "min",
+    "mode",
+    "numOctaves",
+    "offset",
+    "opacity",
+    "operator",
+    "order",
+    "orient",
+    "overflow",
+    "paint-order",
+    "path",
+    "pathLength",
+    "patternContentUnits",
+    "patternTransform",
+    "patternUnits",
+    "pointer-events",
+    "points",
+    "pointsAtX",
+    "pointsAtY",
+    "pointsAtZ",
+    "preserveAlpha",
+    "preserveAspectRatio",
+    "primitiveUnits",
+    "r",
+    "radius",
+    "refX",
+    "refY",
+    "rel",
+    "repeatCount",
+    "repeatDur",
+    "requiredExtensions",
+    "restart",
+    "result",
+    "role",
+    "rotate",
+    "rx",
+    "ry",
+    "scale",
+    "seed",
+    "shape-rendering",
+    "side",
+    "slope",
+    "spacing",
+    "specularConstant",
+    "specularExponent",
+    "spreadMethod",
+    "startOffset",
+    "stdDeviation",
+    "stitchTiles",
+    "stop-color",
+    "stop-opacity",
+    "stroke",
+    "stroke-dasharray",
+    "stroke-dashoffset",
+    "stroke-linecap",
+    "stroke-linejoin",
+    "stroke-miterlimit",
+    "stroke-opacity",
+    "stroke-width",
+    "style",
+    "surfaceScale",
+    "systemLanguage",
+    "tabindex",
+    "tableValues",
+    "target",
+    "targetX",
+    "targetY",
+    "text-anchor",
+    "text-decoration",
+    "text-rendering",
+    "textLength",
+    "to",
+    "transform",
+    "transform-origin",
+    "type",
+    "unicode-bidi",
+    "values",
+    "vector-effect",
+    "version",
+    "viewBox",
+    "visibility",
+    "width",
+    "word-spacing",
+    "writing-mode",
+    "x",
+    "x1",
+    "x2",
+    "xChannelSelector",
+    "xlink:href",
+    "xml:lang",
+    "xml:space",
+    "xmlns",
+    "xmlns:xlink",
+    "y",
+    "y1",
+    "y2",
+    "yChannelSelector",
+    "z",
+];
+
+pub fn is_svg_element(name: &str) -> bool {
+    ELEMENTS.contains(&name)
+}
+
+pub fn is_svg_attribute(name: &str) -> bool {
+    let custom = |prefix: &str| {
+        name.starts_with(prefix)
+            && name.len() > prefix.len()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    };
+
+    ATTRIBUTES.contains(&name) || custom("data-") || custom("aria-")
+}
+
+/// Whether an attribute value could run script, like a `javascript:` link or
+/// an animation that targets an event handler or a link.
+pub fn is_unsafe_value(name: &str, value: &str) -> bool {
+    // browsers ignore whitespace and control characters inside the scheme
+    let value: String = value
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect::<String>()
+        .to_lowercase();
+
+    match name {
+        // animating a link could swap in a `javascript:` url from `to`, `by`
+        // or `values` after the element is checked
+        "attributeName" => value.starts_with("on") || value == "href" || value == "xlink:href",
+        // animation `values` are a list separated by semicolons
+        _ => value
+            .split(';')
+            .any(|item| item.starts_with("javascript:") || item.starts_with("vbscript:")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range() -> Range {
+        create_range(create_pos(0, 0), create_pos(0, 0))
+    }
+
+    #[test]
+    fn serialize_element() {
+        let mut circle = Element::new("circle", range());
+        circle.set_attr("r", "10".to_owned());
+        circle.set_attr("fill", "red".to_owned());
+        circle.set_attr("r", "5".to_owned());
+
+        let mut group = Element::new("g", range());
+        group.children.push(Node::Element(circle));
+        group
+            .children
+            .push(Node::Raw("<path d=\"M0 0\"/>".to_owned(), range()));
+
+        assert_eq!(
+            Node::Element(group).to_string(),
+            "<g><circle r=\"5\" fill=\"red\"/><path d=\"M0 0\"/></g>"
+        );
+    }
+
+    #[test]
+    fn serialize_sorts_children_by_z() {
+        let element = |name: &str, z: f64| {
+            let mut element = Element::new(name, range());
+            element.z = z;
+            Node::Element(element)
+        };
+
+        let mut group = Element::new("g", range());
+        group.children = vec![
+            element("a", 1.0),
+            element("b", 0.0),
+            element("c", -1.0),
+            element("d", 1.0),
+            Node::Raw("<e/>".to_owned(), range()),
+        ];
+
+        assert_eq!(
+            Node::Element(group).to_string(),
+            "<g><c/><b/><e/><a/><d/></g>"
+        );
+    }
+
+    #[test]
+    fn serialize_escapes_attrs_and_text() {
+        let mut text = Element::new("text", range());
+        text.set_attr("fill", "red\" onload=\"alert(1)".to_owned());
+        text.children
+            .push(Node::Text("a < b && c > d \"q\"".to_owned(), range()));
+
+        assert_eq!(
+            Node::Element(text).to_string(),
+            "<text fill=\"red&quot; onload=&quot;alert(1)\">a &lt; b &amp;&amp; c &gt; d \"q\"</text>"
+        );
+    }
+
+    #[test]
+    fn validate_names() {
+        assert!(is_svg_element("circle"));
+        assert!(is_svg_element("linearGradient"));
+        assert!(!is_svg_element("script"));
+        assert!(!is_svg_element("foreignObject"));
+        assert!(!is_svg_element("div"));
+
+        assert!(is_svg_attribute("stroke-width"));
+        assert!(is_svg_attribute("viewBox"));
+        assert!(is_svg_attribute("data-index"));
+        assert!(!is_svg_attribute("data-"));
+        assert!(!is_svg_attribute("data-x\" onload"));
+        assert!(!is_svg_attribute("onload"));
+        assert!(!is_svg_attribute("strokeWidth"));
+    }
+
+    #[test]
+    fn unsafe_values() {
+        assert!(is_unsafe_value("href", "javascript:alert(1)"));
+        assert!(is_unsafe_value("href", " Java\tScript:alert(1)"));
+        assert!(is_unsafe_value("to", "javascript:alert(1)"));
+        assert!(is_unsafe_value("attributeName", "onclick"));
+        assert!(is_unsafe_value("values", "#a;javascript:alert(1)"));
+        assert!(is_unsafe_value("values", "#a; JavaScript:alert(1)"));
+        assert!(is_unsafe_value("attributeName", "href"));
+        assert!(is_unsafe_value("attributeName", "xlink:href"));
+        assert!(!is_unsafe_value("values", "0;1;0"));
+        assert!(!is_unsafe_value("href", "#shape"));
+        assert!(!is_unsafe_value("attributeName", "opacity"));
+    }
+}
@@ -0,0 +1,166 @@
+use crate::lexer::Unit;
+
+use super::error::EvalErrorType::*;
+use super::*;
+
+static TRANSFORMS: &[&str] = &["translate", "rotate", "scale", "skew"];
+
+pub fn is_transform(name: &str) -> bool {
+    TRANSFORMS.contains(&name)
+}
+
+// a distance in user units, which svg transforms take without a unit
+fn get_length(value: Value, pos: Pos) -> EvalResult<f64> {
+    match get_number(value, pos)? {
+        (n, None) | (n, Some(Unit::Px)) => Ok(n),
+        (_, unit) => eval_error(TypeMismatch(unit_name(None), unit_name(unit)), pos),
+    }
+}
+
+// svg transforms take angles in degrees while plain numbers are radians in the
+// trig functions, so transform angles must be given with a unit
+fn get_degrees(value: Value, pos: Pos) -> EvalResult<f64> {
+    match get_number(value, pos)? {
+        (n, Some(unit)) if unit.is_angle() => Ok(to_radians(n, unit).to_degrees()),
+        (_, unit) => eval_error(TypeMismatch("angle".to_owned(), unit_name(unit)), pos),
+    }
+}
+
+fn get_factor(value: Value, pos: Pos) -> EvalResult<f64> {
+    match get_number(value, pos)? {
+        (n, None) => Ok(n),
+        (n, Some(Unit::Percent)) => Ok(n / 100.0),
+        (_, unit) => eval_error(
+            TypeMismatch("number or percentage".to_owned(), unit_name(unit)),
+            pos,
+        ),
+    }
+}
+
+// the value of an optional arg converted with `f`
+fn optional<T>(
+    args: &[(String, Value)],
+    name: &str,
+    pos: Pos,
+    f: fn(Value, Pos) -> EvalResult<T>,
+) -> EvalResult<Option<T>> {
+    match get_field(args, name) {
+        Some(value) => Ok(Some(f(value, pos)?)),
+        None => Ok(None),
+    }
+}
+
+fn required<T>(
+    call: &FunCall,
+    args: &[(String, Value)],
+    name: &str,
+    f: fn(Value, Pos) -> EvalResult<T>,
+) -> EvalResult<T> {
+    match optional(args, name, call.pos(), f)? {
+        Some(value) => Ok(value),
+        None => eval_error(
+            MissingRequiredArg(call.ident.clone(), name.to_owned()),
+            call.pos(),
+        ),
+    }
+}
+
+// the `transform` attribute for a call to one of the transform builtins
+fn eval_transform(call: &FunCall, ctx: &mut Context) -> EvalResult<String> {
+    let pos = call.pos();
+
+    match call.ident.as_str() {
+        // translate(x: 10, y: 20)
+        "translate" => {
+            let args = eval_builtin_args(call, &["x", "y"], ctx)?;
+            let x = optional(&args, "x", pos, get_length)?.unwrap_or(0.0);
+            let y = optional(&args, "y", pos, get_length)?.unwrap_or(0.0);
+            Ok(format!("translate({} {})", x, y))
+        }
+        // rotate(angle: 45deg, around: (50, 50))
+        "rotate" => {
+            let args = eval_builtin_args(call, &["angle", "around"], ctx)?;
+            let angle = required(call, &args, "angle", get_degrees)?;
+            match get_field(&args, "around") {
+                Some(Value::Vec2(x, y, unit)) => Ok(format!(
+                    "rotate({} {} {})",
+                    angle,
+                    get_length(Value::Number(x, unit), pos)?,
+                    get_length(Value::Number(y, unit), pos)?
+                )),
+                Some(v) => eval_error(TypeMismatch("vector".to_owned(), v.name()), pos),
+                None => Ok(format!("rotate({})", angle)),
+            }
+        }
+        // scale(by: 2) or scale(x: 2, y: 50%), where x and y default to `by`
+        "scale" => {
+            let args = eval_builtin_args(call, &["by", "x", "y"], ctx)?;
+            let by = optional(&args, "by", pos, get_factor)?.unwrap_or(1.0);
+            let x = optional(&args, "x", pos, get_factor)?.unwrap_or(by);
+            let y = optional(&args, "y", pos, get_factor)?.unwrap_or(by);
+            Ok(format!("scale({} {})", x, y))
+        }
+        // skew(x: 10deg, y: 5deg) with at least one of the angles
+        "skew" => {
+            let args = eval_builtin_args(call, &["x", "y"], ctx)?;
+            let x = optional(&args, "x", pos, get_degrees)?;
+            let y = optional(&args, "y", pos, get_degrees)?;
+            match (x, y) {
+                (Some(x), Some(y)) => Ok(format!("skewX({}) skewY({})", x, y)),
+                (Some(x), None) => Ok(format!("skewX({})", x)),
+                (None, Some(y)) => Ok(format!("skewY({})", y)),
+                (None, None) => {
+                    eval_error(MissingRequiredArg(call.ident.clone(), "x".to_owned()), pos)
+                }
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+// whether a block is just another transform block, whose group can be folded
+// into the one around it
+fn is_transform_block(block: &Block) -> bool {
+    match block.stmts.as_slice() {
+        [Stmt::Call(call)] => is_transform(&call.ident),
+        _ => false,
+    }
+}
+
+// `translate(...) { rotate(...) { ... } }` gives a single group with both
+// transforms rather than two nested groups. The group keeps the range of the
+// innermost call, which is inside the range of the outer one.
+fn fold_transform(transform: String, mut inner: Element) -> Element {
+    let inner_transform = inner
+        .attrs
+        .iter()
+        .find(|(name, _)| name == "transform")
+        .map(|(_, value)| value.clone())
+        .unwrap_or_default();
+    inner.set_attr("transform", format!("{} {}", transform, inner_transform));
+    inner
+}
+
+/// Wraps the children of a transform block in a `<g>` so they are drawn in a
+/// translated, rotated, scaled or skewed coordinate system.
+pub fn eval_transform_call(call: &FunCall, ctx: &mut Context) -> EvalResult<Vec<Node>> {
+    let transform = eval_transform(call, ctx)?;
+
+    let mut children = match &call.children {
+        Some(block) => eval_block(block, ctx)?,
+        None => return eval_error(MissingChildren(call.ident.clone()), call.pos()),
+    };
+
+    // a transform block always gives exactly one group
+    if matches!(&call.children, Some(block) if is_transform_block(block)) {
+        if let Some(Node::Element(inner)) = children.pop() {
+            return Ok(vec![Node::Element(fold_transform(transform, inner))]);
+        }
+    }
+
+    let mut group = Element::new("g", call.range);
+    group.set_attr("transform", transform);
+    group.children = children;
+
+    Ok(vec![Node::Element(group)])
+}
@@ -7,15 +7,37 @@ use crate::utils::*;
 pub enum LexerErrorType {
     InvalidIdentifier,
     StringNeverTerminated,
+    BlockCommentNeverTerminated,
     UnexpectedCharacter(char),
+    UnknownUnit(String),
+    InvalidColor(String),
+    InvalidNumber(String, Range),
+    UnknownEscape(char),
+    InvalidUnicodeEscape(String),
 }
 
 impl fmt::Display for LexerErrorType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            LexerErrorType::InvalidIdentifier => write!(f, "Identifier must start with a letter"),
+            LexerErrorType::InvalidIdentifier => {
+                write!(f, "Identifier must start with a letter or underscore")
+            }
             LexerErrorType::StringNeverTerminated => write!(f, "String is never terminated"),
+            LexerErrorType::BlockCommentNeverTerminated => {
+                write!(f, "Block comment is never terminated")
+            }
             LexerErrorType::UnexpectedCharacter(c) => write!(f, "Unexpected character `{}`", c),
+            LexerErrorType::UnknownUnit(unit) => write!(f, "Unknown unit `{}`", unit),
+            LexerErrorType::InvalidNumber(number, _) => write!(f, "Invalid number `{}`", number),
+            LexerErrorType::UnknownEscape(c) => write!(f, "Unknown escape sequence `\\{}`", c),
+            LexerErrorType::InvalidUnicodeEscape(digits) => {
+                write!(f, "Invalid unicode escape `\\u{{{}}}`", digits)
+            }
+            LexerErrorType::InvalidColor(hex) => write!(
+                f,
+                "Invalid color `#{}`. Colors are written #rgb, #rrggbb or #rrggbbaa",
+                hex
+            ),
         }
     }
 }
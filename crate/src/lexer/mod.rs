@@ -1,5 +1,7 @@
+use std::fmt;
 use std::iter::Peekable;
 
+use crate::color::Color;
 use crate::error::*;
 use crate::utils::*;
 
@@ -19,23 +21,70 @@ pub enum TokenType {
     RParen,
     LCurly,
     RCurly,
+    LBracket,
+    RBracket,
     Times,
     Divide,
+    Modulo,
+    Power,
+    IntDivide,
     Plus,
     Minus,
     Equals,
     Compare,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+    Bang,
     Colon,
+    Question,
+    Dot,
+    DotDot,
+    Ellipsis,
     Comma,
-    Number(f64),
+    Number(f64, Option<Unit>),
+    Color(Color),
     Ident(String),
     String(String),
+    InterpolatedString(Vec<StringPart>),
+}
+
+/// A piece of a string containing `${expr}` interpolations. Embedded
+/// expressions are lexed in place so their tokens keep their real positions.
+#[derive(Debug, PartialEq, Clone)]
+pub enum StringPart {
+    Literal(String),
+    Expr(Vec<Token>, Range),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Unit {
+    Px,
+    Percent,
+    Deg,
+    Rad,
+    Turn,
+}
+
+/// Source text that is not significant to the parser but is kept around so
+/// tools such as a formatter can reproduce it. The string is the comment body
+/// without its delimiters.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Trivia {
+    LineComment(String, Range),
+    BlockComment(String, Range),
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Token {
     token_type: TokenType,
     token_pos: Range,
+    leading_trivia: Vec<Trivia>,
+    trailing_trivia: Vec<Trivia>,
 }
 
 impl HasPos for Token {
@@ -48,19 +97,78 @@ pub struct Lexer<'a> {
     iter: Peekable<std::str::Chars<'a>>,
     line: u32,
     column: u32,
+    trivia: Vec<Trivia>,
 }
 
 pub fn lex(input: &String) -> LexerResult<Vec<Token>> {
-    let mut tokens = vec![];
+    let mut tokens: Vec<Token> = vec![];
     let mut lexer = Lexer::new(input);
 
-    while let Some(t) = lexer.next()? {
+    while let Some(mut t) = lexer.next()? {
+        // comments that begin on the same line a token ends on belong to that token
+        if let Some(prev) = tokens.last_mut() {
+            let line = prev.token_pos.end.line;
+            let split = t
+                .leading_trivia
+                .iter()
+                .take_while(|trivia| trivia.range().start.line == line)
+                .count();
+            prev.trailing_trivia.extend(t.leading_trivia.drain(..split));
+        }
+
         tokens.push(t);
     }
 
+    // comments at the end of the input have no following token to lead
+    if let Some(last) = tokens.last_mut() {
+        last.trailing_trivia.append(&mut lexer.trivia);
+    }
+
     Ok(tokens)
 }
 
+impl Unit {
+    fn from_suffix(suffix: &str) -> Option<Unit> {
+        match suffix {
+            "px" => Some(Unit::Px),
+            "%" => Some(Unit::Percent),
+            "deg" => Some(Unit::Deg),
+            "rad" => Some(Unit::Rad),
+            "turn" => Some(Unit::Turn),
+            _ => None,
+        }
+    }
+
+    pub fn is_angle(self) -> bool {
+        match self {
+            Unit::Deg | Unit::Rad | Unit::Turn => true,
+            Unit::Px | Unit::Percent => false,
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let suffix = match self {
+            Unit::Px => "px",
+            Unit::Percent => "%",
+            Unit::Deg => "deg",
+            Unit::Rad => "rad",
+            Unit::Turn => "turn",
+        };
+        write!(f, "{}", suffix)
+    }
+}
+
+impl Trivia {
+    pub fn range(&self) -> Range {
+        match self {
+            Trivia::LineComment(_, range) => *range,
+            Trivia::BlockComment(_, range) => *range,
+        }
+    }
+}
+
 impl Token {
     pub fn token_type(&self) -> TokenType {
         self.token_type.clone()
@@ -69,6 +177,17 @@ impl Token {
     pub fn token_pos(&self) -> Range {
         self.token_pos
     }
+
+    // trivia is not used by the compiler itself, only by tooling
+    #[allow(dead_code)]
+    pub fn leading_trivia(&self) -> &[Trivia] {
+        &self.leading_trivia
+    }
+
+    #[allow(dead_code)]
+    pub fn trailing_trivia(&self) -> &[Trivia] {
+        &self.trailing_trivia
+    }
 }
 
 impl<'a> Lexer<'a> {
@@ -77,6 +196,7 @@ impl<'a> Lexer<'a> {
             iter: input.trim().chars().peekable(),
             line: 0,
             column: 0,
+            trivia: vec![],
         }
     }
 
@@ -94,6 +214,8 @@ impl<'a> Lexer<'a> {
                 start: start,
                 end: end,
             },
+            leading_trivia: vec![],
+            trailing_trivia: vec![],
         })
     }
 
@@ -109,6 +231,37 @@ impl<'a> Lexer<'a> {
         Ok(token)
     }
 
+    // a one or two character operator such as `<` and `<=`
+    fn advance_either(
+        &mut self,
+        single: TokenType,
+        next: char,
+        double: TokenType,
+    ) -> LexerResult<Option<Token>> {
+        let start = self.pos();
+
+        self.forward();
+        let token_type = match self.match_next(|c| c == next) {
+            Some(_) => double,
+            None => single,
+        };
+
+        let end = self.pos();
+
+        Ok(self.token(token_type, start, end))
+    }
+
+    fn advance_two(&mut self, token_type: TokenType) -> LexerResult<Option<Token>> {
+        let start = self.pos();
+
+        self.forward();
+        self.forward();
+
+        let end = self.pos();
+
+        Ok(self.token(token_type, start, end))
+    }
+
     fn advance_whitespace(&mut self) -> bool {
         match self.iter.peek() {
             Some(' ') | Some('\t') => {
@@ -131,6 +284,97 @@ impl<'a> Lexer<'a> {
         self.iter.next()
     }
 
+    fn advance_three(&mut self, token_type: TokenType) -> LexerResult<Option<Token>> {
+        let start = self.pos();
+
+        self.forward();
+        self.forward();
+        self.forward();
+
+        let end = self.pos();
+
+        Ok(self.token(token_type, start, end))
+    }
+
+    // look one character past the next one
+    fn peek_second(&self) -> Option<char> {
+        self.peek_nth(1)
+    }
+
+    // advance over a character which may be a newline, keeping the position in sync
+    fn forward_any(&mut self) -> Option<char> {
+        let c = self.forward();
+        if c == Some('\n') {
+            self.line += 1;
+            self.column = 0;
+        }
+        c
+    }
+
+    fn consume_line_comment(&mut self) -> Trivia {
+        let start = self.pos();
+        let mut s = String::new();
+
+        self.forward();
+        self.forward();
+        while let Some(c) = self.match_next(|c| c != '\n' && c != '\r') {
+            s.push(c);
+        }
+
+        Trivia::LineComment(s, create_range(start, self.pos()))
+    }
+
+    fn consume_block_comment(&mut self) -> LexerResult<Trivia> {
+        let start = self.pos();
+        let mut s = String::new();
+        let mut depth = 1;
+
+        self.forward();
+        self.forward();
+        loop {
+            match (self.iter.peek().cloned(), self.peek_second()) {
+                (None, _) => return lexer_error(BlockCommentNeverTerminated, start),
+                (Some('/'), Some('*')) => {
+                    depth += 1;
+                    self.forward();
+                    self.forward();
+                    s.push_str("/*");
+                }
+                (Some('*'), Some('/')) => {
+                    depth -= 1;
+                    self.forward();
+                    self.forward();
+                    if depth == 0 {
+                        break;
+                    }
+                    s.push_str("*/");
+                }
+                (Some(_), _) => s.push(self.forward_any().unwrap()),
+            }
+        }
+
+        Ok(Trivia::BlockComment(s, create_range(start, self.pos())))
+    }
+
+    // skip whitespace, collecting any comments into the pending trivia
+    fn consume_trivia(&mut self) -> LexerResult<()> {
+        loop {
+            while self.advance_whitespace() {}
+
+            match (self.iter.peek().cloned(), self.peek_second()) {
+                (Some('/'), Some('/')) => {
+                    let comment = self.consume_line_comment();
+                    self.trivia.push(comment);
+                }
+                (Some('/'), Some('*')) => {
+                    let comment = self.consume_block_comment()?;
+                    self.trivia.push(comment);
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
     fn match_next(&mut self, pred: impl Fn(char) -> bool) -> Option<char> {
         let c = match self.iter.peek() {
             Some(c) => *c,
@@ -145,41 +389,242 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.iter.clone().nth(n)
+    }
+
+    fn at_triple_quote(&self) -> bool {
+        (0..3).all(|i| self.peek_nth(i) == Some('"'))
+    }
+
+    // whether only whitespace is left before closing triple quotes on this line
+    fn at_closing_quote_line(&self) -> bool {
+        let mut iter = self.iter.clone().skip_while(|c| *c == ' ' || *c == '\t');
+        (0..3).all(|_| iter.next() == Some('"'))
+    }
+
+    fn skip_indent(&mut self, indent: usize) {
+        for _ in 0..indent {
+            if self.match_next(|c| c == ' ' || c == '\t').is_none() {
+                break;
+            }
+        }
+    }
+
+    // The indentation shared by every non-blank line of a triple quoted string,
+    // including the line with the closing quotes. Starts at the opening quotes.
+    fn triple_quote_indent(&self) -> usize {
+        let chars: Vec<char> = self.iter.clone().collect();
+        let mut indent = usize::MAX;
+        let mut i = 3;
+
+        while i < chars.len() {
+            match chars[i] {
+                '\\' => i += 2,
+                '"' if chars[i..].starts_with(&['"', '"', '"']) => break,
+                '\n' => {
+                    i += 1;
+                    let width = chars[i..]
+                        .iter()
+                        .take_while(|c| **c == ' ' || **c == '\t')
+                        .count();
+                    match chars.get(i + width) {
+                        Some('\n') | None => (),
+                        Some(_) => indent = indent.min(width),
+                    }
+                    i += width;
+                }
+                _ => i += 1,
+            }
+        }
+
+        if indent == usize::MAX {
+            0
+        } else {
+            indent
+        }
+    }
+
+    fn consume_escape(&mut self, escape_start: Pos, string_start: Pos) -> LexerResult<char> {
+        match self.forward() {
+            None => lexer_error(StringNeverTerminated, string_start),
+            Some('n') => Ok('\n'),
+            Some('t') => Ok('\t'),
+            Some('r') => Ok('\r'),
+            Some('0') => Ok('\0'),
+            Some(c) if c == '\\' || c == '"' || c == '$' => Ok(c),
+            Some('u') => self.consume_unicode_escape(escape_start),
+            Some(c) => lexer_error(UnknownEscape(c), escape_start),
+        }
+    }
+
+    // a `\u{...}` escape of one to six hex digits, after the `u`
+    fn consume_unicode_escape(&mut self, escape_start: Pos) -> LexerResult<char> {
+        let mut digits = String::new();
+
+        if self.match_next(|c| c == '{').is_some() {
+            while let Some(c) = self.match_next(|c| c != '}' && c != '"' && c != '\n') {
+                digits.push(c);
+            }
+
+            if self.match_next(|c| c == '}').is_some() && digits.len() <= 6 {
+                let c = u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(std::char::from_u32);
+                if let Some(c) = c {
+                    return Ok(c);
+                }
+            }
+        }
+
+        lexer_error(InvalidUnicodeEscape(digits), escape_start)
+    }
+
     fn consume_string(&mut self) -> LexerResult<Option<Token>> {
         let start = self.pos();
         let mut s = String::new();
+        let mut parts: Vec<StringPart> = vec![];
+
+        let triple = self.at_triple_quote();
+        let indent = if triple {
+            self.triple_quote_indent()
+        } else {
+            0
+        };
+
+        if triple {
+            self.forward();
+            self.forward();
+            self.forward();
+
+            // the line break after the opening quotes is not part of the string
+            if self.match_next(|c| c == '\n').is_some() {
+                self.line += 1;
+                self.column = 0;
+                self.skip_indent(indent);
+            }
+        } else {
+            self.forward();
+        }
 
-        self.forward();
         loop {
+            if self.iter.peek() == Some(&'$') && self.peek_second() == Some('{') {
+                parts.push(StringPart::Literal(std::mem::take(&mut s)));
+                parts.push(self.consume_interpolation(start)?);
+                continue;
+            }
+
+            if triple && self.at_triple_quote() {
+                self.forward();
+                self.forward();
+                self.forward();
+                break;
+            }
+
+            let escape_start = self.pos();
             let c = self.forward();
 
             match c {
                 None => return lexer_error(StringNeverTerminated, start),
-                Some('\\') => {
-                    let c = self.forward();
-                    match c {
-                        None => return lexer_error(StringNeverTerminated, start),
-                        Some(c) => s.push(c),
-                    };
-                }
-                Some('"') => break,
-                Some(c) => {
-                    s.push(c);
+                Some('\\') => s.push(self.consume_escape(escape_start, start)?),
+                Some('"') if !triple => break,
+                Some('\n') => {
+                    self.line += 1;
+                    self.column = 0;
 
-                    if c == '\n' {
-                        self.line += 1;
-                        self.column = 0;
+                    if !triple {
+                        s.push('\n');
+                    } else if self.at_closing_quote_line() {
+                        // neither is the line break before the closing quotes
+                        while self.match_next(|c| c == ' ' || c == '\t').is_some() {}
+                    } else {
+                        s.push('\n');
+                        self.skip_indent(indent);
                     }
                 }
+                Some(c) => s.push(c),
             }
         }
 
         let end = self.pos();
 
-        let token = self.token(TokenType::String(s), start, end);
+        let token_type = if parts.is_empty() {
+            TokenType::String(s)
+        } else {
+            parts.push(StringPart::Literal(s));
+            parts.retain(|part| part != &StringPart::Literal(String::new()));
+            TokenType::InterpolatedString(parts)
+        };
+
+        let token = self.token(token_type, start, end);
         Ok(token)
     }
 
+    fn is_raw_string_start(&self) -> bool {
+        let hashes = self.iter.clone().skip(1).take_while(|c| *c == '#').count();
+        self.peek_nth(hashes + 1) == Some('"')
+    }
+
+    // r"..." or r#"..."# with any number of #, with no escapes or interpolation
+    fn consume_raw_string(&mut self) -> LexerResult<Option<Token>> {
+        let start = self.pos();
+        let mut s = String::new();
+        let mut hashes = 0;
+
+        self.forward();
+        while self.match_next(|c| c == '#').is_some() {
+            hashes += 1;
+        }
+        self.forward();
+
+        loop {
+            match self.forward_any() {
+                None => return lexer_error(StringNeverTerminated, start),
+                Some('"') if (0..hashes).all(|i| self.peek_nth(i) == Some('#')) => {
+                    for _ in 0..hashes {
+                        self.forward();
+                    }
+                    break;
+                }
+                Some(c) => s.push(c),
+            }
+        }
+
+        let end = self.pos();
+
+        Ok(self.token(TokenType::String(s), start, end))
+    }
+
+    // lex the tokens of a `${...}` up to its matching closing curly
+    fn consume_interpolation(&mut self, string_start: Pos) -> LexerResult<StringPart> {
+        let start = self.pos();
+        let mut tokens: Vec<Token> = vec![];
+        let mut depth = 0;
+
+        self.forward();
+        self.forward();
+        loop {
+            let token = match self.next()? {
+                Some(token) => token,
+                None => return lexer_error(StringNeverTerminated, string_start),
+            };
+
+            match token.token_type {
+                TokenType::LCurly => depth += 1,
+                TokenType::RCurly if depth == 0 => {
+                    return Ok(StringPart::Expr(
+                        tokens,
+                        create_range(start, token.token_pos.end),
+                    ))
+                }
+                TokenType::RCurly => depth -= 1,
+                _ => (),
+            };
+
+            tokens.push(token);
+        }
+    }
+
     fn consume_ident(&mut self) -> LexerResult<Option<Token>> {
         let start = self.pos();
         let mut id = String::new();
@@ -198,87 +643,218 @@ impl<'a> Lexer<'a> {
         Ok(token)
     }
 
+    fn consume_digits(&mut self, s: &mut String) {
+        while let Some(c) = self.match_next(|c| is_digit(c) || c == '_') {
+            s.push(c);
+        }
+    }
+
     fn consume_number(&mut self) -> LexerResult<Option<Token>> {
         let start = self.pos();
-
         let mut str = String::new();
-        let mut seen_dot = false;
-
-        while let Some(c) = self.iter.peek() {
-            if *c == '.' && !seen_dot {
-                seen_dot = true;
-                str.push(self.forward().unwrap());
-            } else if is_digit(*c) {
-                str.push(self.forward().unwrap());
-            } else {
-                break;
+
+        let radix = match (self.iter.peek().cloned(), self.peek_second()) {
+            (Some('0'), Some('x')) | (Some('0'), Some('X')) => Some(16),
+            (Some('0'), Some('b')) | (Some('0'), Some('B')) => Some(2),
+            _ => None,
+        };
+
+        if let Some(radix) = radix {
+            str.push(self.forward().unwrap());
+            str.push(self.forward().unwrap());
+            while let Some(c) = self.match_next(is_alphanum) {
+                str.push(c);
             }
+
+            let end = self.pos();
+
+            return match parse_integer(&str[2..], radix) {
+                Some(n) => Ok(self.token(TokenType::Number(n, None), start, end)),
+                None => lexer_error(InvalidNumber(str, create_range(start, end)), start),
+            };
         }
 
+        self.consume_digits(&mut str);
+
+        if self.iter.peek() == Some(&'.') {
+            match self.peek_second() {
+                Some(c) if is_digit(c) => {
+                    str.push(self.forward().unwrap());
+                    self.consume_digits(&mut str);
+                }
+                // a range or member access rather than a decimal point
+                Some(c) if c == '.' || is_alpha(c) => (),
+                _ => str.push(self.forward().unwrap()),
+            }
+        }
+
+        if let Some(c) = self.match_next(|c| c == 'e' || c == 'E') {
+            str.push(c);
+            if let Some(c) = self.match_next(|c| c == '+' || c == '-') {
+                str.push(c);
+            }
+            self.consume_digits(&mut str);
+        }
+
+        let n = match parse_decimal(&str) {
+            Some(n) => n,
+            None => {
+                let range = create_range(start, self.pos());
+                return lexer_error(InvalidNumber(str, range), start);
+            }
+        };
+
+        let unit = self.consume_unit()?;
+
         let end = self.pos();
 
-        let n = str.parse::<f64>().unwrap();
+        Ok(self.token(TokenType::Number(n, unit), start, end))
+    }
+
+    fn consume_color(&mut self) -> LexerResult<Option<Token>> {
+        let start = self.pos();
+        let mut hex = String::new();
+
+        self.forward();
+        while let Some(c) = self.match_next(is_alphanum) {
+            hex.push(c);
+        }
+
+        let end = self.pos();
 
-        Ok(self.token(TokenType::Number(n), start, end))
+        match Color::from_hex(&hex) {
+            Some(color) => Ok(self.token(TokenType::Color(color), start, end)),
+            None => lexer_error(InvalidColor(hex), start),
+        }
+    }
+
+    // an optional unit suffix directly following a number
+    fn consume_unit(&mut self) -> LexerResult<Option<Unit>> {
+        let start = self.pos();
+        let mut suffix = String::new();
+
+        // `10%3` and `10%-3` are a modulo but `10%` and `10% 3` are percentages
+        let is_percent = self.iter.peek() == Some(&'%')
+            && !self
+                .peek_second()
+                .is_some_and(|c| is_alphanum(c) || "(.\"#+-".contains(c));
+
+        if is_percent {
+            suffix.push(self.forward().unwrap());
+        } else {
+            while let Some(c) = self.match_next(is_alpha) {
+                suffix.push(c);
+            }
+        }
+
+        if suffix.is_empty() {
+            return Ok(None);
+        }
+
+        match Unit::from_suffix(&suffix) {
+            Some(unit) => Ok(Some(unit)),
+            None => lexer_error(UnknownUnit(suffix), start),
+        }
     }
 
     fn next(&mut self) -> LexerResult<Option<Token>> {
-        // ignore whitespace
-        while self.advance_whitespace() {}
+        // ignore whitespace and comments
+        self.consume_trivia()?;
 
         let c = match self.iter.peek() {
-            Some(c) => c,
+            Some(c) => *c,
             None => return Ok(None),
         };
 
-        let token = match c {
+        // taken before lexing the token as strings lex the tokens they embed
+        let leading_trivia = std::mem::take(&mut self.trivia);
+
+        let mut token = match c {
             '(' => self.advance(TokenType::LParen),
             ')' => self.advance(TokenType::RParen),
             '{' => self.advance(TokenType::LCurly),
+            '[' => self.advance(TokenType::LBracket),
+            ']' => self.advance(TokenType::RBracket),
             '}' => self.advance(TokenType::RCurly),
-            '*' => self.advance(TokenType::Times),
+            '*' => self.advance_either(TokenType::Times, '*', TokenType::Power),
+            '%' => self.advance(TokenType::Modulo),
+            '~' if self.peek_second() == Some('/') => self.advance_two(TokenType::IntDivide),
             '/' => self.advance(TokenType::Divide),
             '+' => self.advance(TokenType::Plus),
             '-' => self.advance(TokenType::Minus),
             ':' => self.advance(TokenType::Colon),
+            '?' => self.advance(TokenType::Question),
             ',' => self.advance(TokenType::Comma),
-            '=' => {
-                let start = self.pos();
-                self.forward();
-                let end = self.pos();
-                match self.iter.peek() {
-                    Some('=') => self.advance(TokenType::Compare),
-                    _ => Ok(self.token(TokenType::Equals, start, end)),
-                }
-            }
+            '=' => self.advance_either(TokenType::Equals, '=', TokenType::Compare),
+            '!' => self.advance_either(TokenType::Bang, '=', TokenType::NotEqual),
+            '<' => self.advance_either(TokenType::Less, '=', TokenType::LessEqual),
+            '>' => self.advance_either(TokenType::Greater, '=', TokenType::GreaterEqual),
+            '&' if self.peek_second() == Some('&') => self.advance_two(TokenType::And),
+            '|' if self.peek_second() == Some('|') => self.advance_two(TokenType::Or),
             '"' => self.consume_string(),
-            'a'...'z' => self.consume_ident(),
-            '0'...'9' => self.consume_number(),
-            _ => return lexer_error(UnexpectedCharacter(*c), self.pos()),
-        };
+            '#' => self.consume_color(),
+            '0'..='9' => self.consume_number(),
+            '.' if self.peek_second() == Some('.') && self.peek_nth(2) == Some('.') => {
+                self.advance_three(TokenType::Ellipsis)
+            }
+            '.' if self.peek_second() == Some('.') => self.advance_two(TokenType::DotDot),
+            '.' if self.peek_second().is_some_and(is_digit) => self.consume_number(),
+            '.' => self.advance(TokenType::Dot),
+            'r' if self.is_raw_string_start() => self.consume_raw_string(),
+            c if is_alpha(c) => self.consume_ident(),
+            c => return lexer_error(UnexpectedCharacter(c), self.pos()),
+        }?;
+
+        if let Some(token) = token.as_mut() {
+            token.leading_trivia = leading_trivia;
+        }
 
-        token
+        Ok(token)
     }
 }
 
 fn is_alpha(c: char) -> bool {
-    match c {
-        'a'...'z' | 'A'...'Z' => true,
-        _ => false,
-    }
+    c.is_ascii_alphabetic() || c == '_'
 }
 
 fn is_digit(c: char) -> bool {
-    match c {
-        '0'...'9' => true,
-        _ => false,
-    }
+    c.is_ascii_digit()
 }
 
 fn is_alphanum(c: char) -> bool {
     is_alpha(c) || is_digit(c)
 }
 
+// `_` may only be used to separate two digits
+fn has_valid_separators(s: &str, is_digit: impl Fn(char) -> bool) -> bool {
+    let chars: Vec<char> = s.chars().collect();
+    chars.iter().enumerate().all(|(i, c)| {
+        *c != '_'
+            || (i > 0 && i + 1 < chars.len() && is_digit(chars[i - 1]) && is_digit(chars[i + 1]))
+    })
+}
+
+fn parse_decimal(s: &str) -> Option<f64> {
+    if !has_valid_separators(s, is_digit) {
+        return None;
+    }
+
+    s.replace('_', "")
+        .parse::<f64>()
+        .ok()
+        .filter(|n| n.is_finite())
+}
+
+fn parse_integer(digits: &str, radix: u32) -> Option<f64> {
+    if digits.is_empty() || !has_valid_separators(digits, |c| c.is_digit(radix)) {
+        return None;
+    }
+
+    u64::from_str_radix(&digits.replace('_', ""), radix)
+        .ok()
+        .map(|n| n as f64)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -290,6 +866,18 @@ mod tests {
         assert_debug_snapshot_matches!(tokens);
     }
 
+    #[test]
+    fn lex_uppercase_identifier() {
+        let tokens = lex(&"Logo".to_owned());
+        assert_debug_snapshot_matches!(tokens);
+    }
+
+    #[test]
+    fn lex_underscore_identifier() {
+        let tokens = lex(&"_tmp stroke_width _ a_1".to_owned());
+        assert_debug_snapshot_matches!(tokens);
+    }
+
     #[test]
     fn lex_integer() {
         let tokens = lex(&"10".to_owned());
@@ -302,6 +890,72 @@ mod tests {
         assert_debug_snapshot_matches!(tokens);
     }
 
+    #[test]
+    fn lex_number_formats() {
+        let tokens = lex(&"1e3 2.5E-2 .5 1. 1_000 0xff 0b1010 1e2px".to_owned());
+        assert_debug_snapshot_matches!(tokens);
+    }
+
+    #[test]
+    fn lex_invalid_numbers() {
+        let check = |input: &str, end: u32| {
+            let tokens = lex(&input.to_owned());
+            let range = create_range(create_pos(0, 0), create_pos(0, end));
+            assert_eq!(
+                tokens,
+                Err(LexerError::new(
+                    InvalidNumber(input.to_owned(), range),
+                    create_pos(0, 0)
+                ))
+            );
+        };
+
+        check("1e", 2);
+        check("1e+", 3);
+        check("1__0", 4);
+        check("1_", 2);
+        check("0x", 2);
+        check("0xfg", 4);
+        check("0b102", 5);
+        check("1e999", 5);
+    }
+
+    #[test]
+    fn lex_units() {
+        let tokens = lex(&"1px 50% 45deg 1.5rad 0.25turn".to_owned());
+        assert_debug_snapshot_matches!(tokens);
+    }
+
+    #[test]
+    fn lex_unknown_unit() {
+        let tokens = lex(&"10 12pt".to_owned());
+        assert_eq!(
+            tokens,
+            Err(LexerError::new(
+                UnknownUnit("pt".to_owned()),
+                create_pos(0, 5)
+            ))
+        );
+    }
+
+    #[test]
+    fn lex_colors() {
+        let tokens = lex(&"#f0a #ff0000 #00ff0080".to_owned());
+        assert_debug_snapshot_matches!(tokens);
+    }
+
+    #[test]
+    fn lex_invalid_color() {
+        let tokens = lex(&"#ff00".to_owned());
+        assert_eq!(
+            tokens,
+            Err(LexerError::new(
+                InvalidColor("ff00".to_owned()),
+                create_pos(0, 0)
+            ))
+        );
+    }
+
     #[test]
     fn lex_string() {
         let tokens = lex(&"\"hello\"".to_owned());
@@ -316,6 +970,83 @@ mod tests {
         assert_debug_snapshot_matches!(tokens);
     }
 
+    #[test]
+    fn lex_interpolated_string() {
+        let tokens = lex(&"\"cx=${cx + 1}, ${ {} }\\${x}\"".to_owned());
+        assert_debug_snapshot_matches!(tokens);
+    }
+
+    #[test]
+    fn lex_unterminated_interpolation() {
+        let tokens = lex(&"\"a ${b\"".to_owned());
+        assert_eq!(
+            tokens,
+            Err(LexerError::new(StringNeverTerminated, create_pos(0, 6)))
+        );
+    }
+
+    #[test]
+    fn lex_escape_sequences() {
+        let tokens = lex(&r#""a\n\t\\\"\$\u{1F600}\u{e9}""#.to_owned());
+        assert_eq!(
+            tokens.unwrap()[0].token_type(),
+            TokenType::String("a\n\t\\\"$\u{1F600}\u{e9}".to_owned())
+        );
+    }
+
+    #[test]
+    fn lex_invalid_escapes() {
+        let tokens = lex(&r#""ab\q""#.to_owned());
+        assert_eq!(
+            tokens,
+            Err(LexerError::new(UnknownEscape('q'), create_pos(0, 3)))
+        );
+
+        let tokens = lex(&r#""\u{110000}""#.to_owned());
+        assert_eq!(
+            tokens,
+            Err(LexerError::new(
+                InvalidUnicodeEscape("110000".to_owned()),
+                create_pos(0, 1)
+            ))
+        );
+
+        let tokens = lex(&r#""\u41""#.to_owned());
+        assert_eq!(
+            tokens,
+            Err(LexerError::new(
+                InvalidUnicodeEscape("".to_owned()),
+                create_pos(0, 1)
+            ))
+        );
+    }
+
+    #[test]
+    fn lex_raw_string() {
+        let tokens = lex(&r###"r"C:\path" r#"<g fill="red">"# r##"a "# b"##"###.to_owned());
+        let types: Vec<TokenType> = tokens.unwrap().iter().map(|t| t.token_type()).collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::String("C:\\path".to_owned()),
+                TokenType::String("<g fill=\"red\">".to_owned()),
+                TokenType::String("a \"# b".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn lex_triple_quoted_string() {
+        let code = "svg(value: \"\"\"
+    <circle
+      cx=\"${cx}\"
+    />
+
+    \"\"\")";
+        let tokens = lex(&code.to_owned());
+        assert_debug_snapshot_matches!(tokens);
+    }
+
     #[test]
     fn lex_multiline_string() {
         let tokens = lex(&"\"\nhello\"".to_owned());
@@ -328,6 +1059,12 @@ mod tests {
         assert_debug_snapshot_matches!(tokens);
     }
 
+    #[test]
+    fn lex_brackets() {
+        let tokens = lex(&"[]".to_owned());
+        assert_debug_snapshot_matches!(tokens);
+    }
+
     #[test]
     fn lex_curlies() {
         let tokens = lex(&"{}".to_owned());
@@ -340,9 +1077,132 @@ mod tests {
         assert_debug_snapshot_matches!(tokens);
     }
 
+    #[test]
+    fn lex_logical_operators() {
+        let tokens = lex(&"! != < <= > >= && ||".to_owned());
+        assert_debug_snapshot_matches!(tokens);
+    }
+
+    #[test]
+    fn lex_single_ampersand() {
+        let tokens = lex(&"a & b".to_owned());
+        assert_eq!(
+            tokens,
+            Err(LexerError::new(UnexpectedCharacter('&'), create_pos(0, 2)))
+        );
+    }
+
+    #[test]
+    fn lex_arithmetic_operators() {
+        let tokens = lex(&"10%3 ** 2 ~/ 4 % 50%".to_owned()).map(|tokens| {
+            tokens
+                .iter()
+                .map(|t| t.token_type())
+                .collect::<Vec<TokenType>>()
+        });
+        assert_eq!(
+            tokens,
+            Ok(vec![
+                TokenType::Number(10.0, None),
+                TokenType::Modulo,
+                TokenType::Number(3.0, None),
+                TokenType::Power,
+                TokenType::Number(2.0, None),
+                TokenType::IntDivide,
+                TokenType::Number(4.0, None),
+                TokenType::Modulo,
+                TokenType::Number(50.0, Some(Unit::Percent)),
+            ])
+        );
+    }
+
+    #[test]
+    fn lex_percent_or_modulo() {
+        let check = |line: &str, expected: Vec<TokenType>| {
+            let tokens = lex(&line.to_owned()).map(|tokens| {
+                tokens
+                    .iter()
+                    .map(|t| t.token_type())
+                    .collect::<Vec<TokenType>>()
+            });
+            assert_eq!(tokens, Ok(expected), "{}", line);
+        };
+
+        let modulo = |rhs: Vec<TokenType>| {
+            let mut tokens = vec![TokenType::Number(10.0, None), TokenType::Modulo];
+            tokens.extend(rhs);
+            tokens
+        };
+
+        check("10%3", modulo(vec![TokenType::Number(3.0, None)]));
+        check(
+            "10%-3",
+            modulo(vec![TokenType::Minus, TokenType::Number(3.0, None)]),
+        );
+        check(
+            "10%+3",
+            modulo(vec![TokenType::Plus, TokenType::Number(3.0, None)]),
+        );
+        check(
+            "10% 3",
+            vec![
+                TokenType::Number(10.0, Some(Unit::Percent)),
+                TokenType::Number(3.0, None),
+            ],
+        );
+        check(
+            "10% - 3",
+            vec![
+                TokenType::Number(10.0, Some(Unit::Percent)),
+                TokenType::Minus,
+                TokenType::Number(3.0, None),
+            ],
+        );
+    }
+
     #[test]
     fn lex_special() {
-        let tokens = lex(&":,".to_owned());
+        let tokens = lex(&":,?".to_owned());
+        assert_debug_snapshot_matches!(tokens);
+    }
+
+    #[test]
+    fn lex_dots() {
+        let tokens = lex(&"p.x ...r 1.".to_owned());
         assert_debug_snapshot_matches!(tokens);
     }
+
+    #[test]
+    fn lex_range() {
+        let tokens = lex(&"0..10 .5..1.5".to_owned());
+        assert_debug_snapshot_matches!(tokens);
+    }
+
+    #[test]
+    fn lex_line_comment() {
+        let code = "// leading
+a // trailing
+b
+// end";
+        let tokens = lex(&code.to_owned());
+        assert_debug_snapshot_matches!(tokens);
+    }
+
+    #[test]
+    fn lex_block_comment() {
+        let tokens = lex(&"a /* one /* two */\n */ / b".to_owned());
+        assert_debug_snapshot_matches!(tokens);
+    }
+
+    #[test]
+    fn lex_unterminated_block_comment() {
+        let tokens = lex(&"a /* /* */ b".to_owned());
+        assert_eq!(
+            tokens,
+            Err(LexerError::new(
+                BlockCommentNeverTerminated,
+                create_pos(0, 2)
+            ))
+        );
+    }
 }
@@ -1,6 +1,7 @@
 use std::fmt;
 use wasm_bindgen::prelude::*;
 
+mod color;
 mod error;
 mod interpret;
 mod lexer;
@@ -52,6 +53,58 @@ impl CompileResult {
     }
 }
 
+#[wasm_bindgen]
+#[derive(Clone, Default)]
+pub struct CompileOptions {
+    seed: Option<f64>,
+    raw_svg: bool,
+    canvas: interpret::Canvas,
+}
+
+#[wasm_bindgen]
+impl CompileOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> CompileOptions {
+        CompileOptions::default()
+    }
+
+    /// Overrides the `seed` declared in the program.
+    pub fn set_seed(&mut self, seed: f64) {
+        self.seed = Some(seed);
+    }
+
+    /// Allows `raw_svg(value: ...)` to write markup that is not escaped. Only
+    /// enable this for trusted programs.
+    pub fn set_raw_svg(&mut self, raw_svg: bool) {
+        self.raw_svg = raw_svg;
+    }
+
+    /// Overrides the canvas width, in the canvas units. Like a `canvas { }`
+    /// width it cannot be negative.
+    pub fn set_width(&mut self, width: f64) {
+        self.canvas.width = Some(width);
+    }
+
+    /// Overrides the canvas height, in the canvas units. Like a `canvas { }`
+    /// height it cannot be negative.
+    pub fn set_height(&mut self, height: f64) {
+        self.canvas.height = Some(height);
+    }
+
+    pub fn set_view_box(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        self.canvas.view_box = Some([x, y, width, height]);
+    }
+
+    pub fn set_background(&mut self, background: String) {
+        self.canvas.background = Some(background);
+    }
+
+    /// One of `px`, `mm`, `cm`, `in`, `pt` or `pc`.
+    pub fn set_units(&mut self, units: String) {
+        self.canvas.units = Some(units);
+    }
+}
+
 #[wasm_bindgen]
 pub struct Foo {
     test: String,
@@ -89,6 +142,11 @@ fn error_to_compile_result<T: fmt::Display>(err: error::Error<T>) -> CompileResu
 
 #[wasm_bindgen]
 pub fn compile(input: &str) -> CompileResult {
+    compile_with_options(input, &CompileOptions::new())
+}
+
+#[wasm_bindgen]
+pub fn compile_with_options(input: &str, options: &CompileOptions) -> CompileResult {
     let tokens = match lexer::lex(&input.to_owned()) {
         Ok(tokens) => tokens,
         Err(err) => return error_to_compile_result(err),
@@ -99,7 +157,13 @@ pub fn compile(input: &str) -> CompileResult {
         Err(err) => return error_to_compile_result(err),
     };
 
-    let result = match interpret::eval_program(&program) {
+    let options = interpret::Options {
+        seed: options.seed,
+        raw_svg: options.raw_svg,
+        canvas: options.canvas.clone(),
+    };
+
+    let result = match interpret::eval_program(&program, &options) {
         Ok(value) => value,
         Err(err) => return error_to_compile_result(err),
     };
@@ -1,5 +1,6 @@
 use std::io::{self, Read};
 
+mod color;
 mod error;
 mod interpret;
 mod lexer;
@@ -28,7 +29,7 @@ fn main() -> io::Result<()> {
         }
     };
 
-    let result = match interpret::eval_program(&program) {
+    let result = match interpret::eval_program(&program, &interpret::Options::default()) {
         Ok(value) => value,
         Err(err) => {
             println!("{}", err);
@@ -1,3 +1,5 @@
+use crate::color::Color;
+use crate::lexer::Unit;
 use crate::utils::*;
 
 pub type Ident = String;
@@ -8,9 +10,32 @@ pub struct Program {
     pub end: Pos,
 }
 
+// every declaration kind is named `*Decl` to match the original `ShapeDecl`
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, PartialEq, Clone)]
 pub enum Decl {
     ShapeDecl(Shape),
+    ConstDecl(Const),
+    FnDecl(Function),
+    SeedDecl(Expr, Pos),
+    // a record describing the root svg element
+    CanvasDecl(Expr, Pos),
+    LayerDecl(Layer),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Function {
+    pub name: Ident,
+    pub args: Vec<Arg>,
+    pub expr: Expr,
+    pub pos: Pos,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Const {
+    pub name: Ident,
+    pub expr: Expr,
+    pub pos: Pos,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -21,6 +46,14 @@ pub struct Shape {
     pub pos: Pos,
 }
 
+// a named top-level group drawn after `main`
+#[derive(Debug, PartialEq, Clone)]
+pub struct Layer {
+    pub name: Ident,
+    pub block: Block,
+    pub pos: Pos,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Arg {
     pub name: Ident,
@@ -29,7 +62,7 @@ pub struct Arg {
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Block {
-    pub calls: Vec<FunCall>,
+    pub stmts: Vec<Stmt>,
     pub range: Range,
 }
 
@@ -39,9 +72,13 @@ pub struct NamedArg {
     pub expr: Expr,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Stmt {
-    Expr(Expr, Pos),
+    Call(FunCall),
+    // an `else if` is an else block containing a single if statement
+    If(Expr, Block, Option<Block>, Range),
+    For(Ident, Expr, Block, Range),
+    Let(Ident, Expr, Pos),
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -51,32 +88,71 @@ pub enum Expr {
     Binary(Box<Expr>, BinOp, Box<Expr>, Pos),
     Unary(UnOp, Box<Expr>, Pos),
     Grouping(Box<Expr>),
+    Tuple(Box<Expr>, Box<Expr>, Range),
+    Call(Ident, Vec<Expr>, Range),
+    Interpolated(Vec<InterpolatedPart>, Range),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>, Pos),
+    Range(Box<Expr>, Box<Expr>, Option<Box<Expr>>, Pos),
+    List(Vec<Expr>, Range),
+    Index(Box<Expr>, Box<Expr>, Pos),
+    Record(Vec<RecordEntry>, Range),
+    Field(Box<Expr>, Ident, Pos),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum RecordEntry {
+    Field(Ident, Expr),
+    Spread(Expr),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum InterpolatedPart {
+    Literal(String),
+    Expr(Expr),
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct FunCall {
     pub ident: Ident,
     pub args: Vec<NamedArg>,
+    // records whose fields are used for args that are not given by name
+    pub spreads: Vec<Expr>,
+    // the child statements of builtins like `element(...) { ... }`
+    pub children: Option<Block>,
     pub range: Range,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Literal {
-    Number(f64),
+    Number(f64, Option<Unit>),
+    Color(Color),
     String(String),
+    Bool(bool),
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum BinOp {
     Mul,
     Div,
+    IntDiv,
+    Mod,
+    Pow,
     Add,
     Sub,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    And,
+    Or,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum UnOp {
     Neg,
+    Not,
 }
 
 pub fn prec(p: Precedence) -> u32 {
@@ -86,10 +162,16 @@ pub fn prec(p: Precedence) -> u32 {
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum Precedence {
     Conditional = 20,
+    Range = 21,
+    Or = 22,
+    And = 24,
+    Equality = 26,
+    Comparison = 28,
     Sum = 30,
     Product = 40,
+    // below `**` so that `-2 ** 2` is `-(2 ** 2)`
+    Prefix = 45,
     Exponent = 50,
-    Prefix = 60,
     Postfix = 70,
     Call = 80,
 }
@@ -102,6 +184,15 @@ impl HasPos for Expr {
             Expr::Binary(_, _, _, p) => *p,
             Expr::Unary(_, _, p) => *p,
             Expr::Grouping(ref e) => e.pos(),
+            Expr::Tuple(_, _, range) => range.start,
+            Expr::Call(_, _, range) => range.start,
+            Expr::Interpolated(_, range) => range.start,
+            Expr::Conditional(_, _, _, p) => *p,
+            Expr::Range(_, _, _, p) => *p,
+            Expr::List(_, range) => range.start,
+            Expr::Index(_, _, p) => *p,
+            Expr::Record(_, range) => range.start,
+            Expr::Field(_, _, p) => *p,
         }
     }
 }
@@ -109,7 +200,10 @@ impl HasPos for Expr {
 impl HasPos for Stmt {
     fn pos(&self) -> Pos {
         match self {
-            Stmt::Expr(_, p) => *p,
+            Stmt::Call(call) => call.pos(),
+            Stmt::If(_, _, _, range) => range.start,
+            Stmt::For(_, _, _, range) => range.start,
+            Stmt::Let(_, _, p) => *p,
         }
     }
 }
@@ -120,6 +214,24 @@ impl HasPos for FunCall {
     }
 }
 
+impl HasPos for Function {
+    fn pos(&self) -> Pos {
+        self.pos
+    }
+}
+
+impl HasPos for Layer {
+    fn pos(&self) -> Pos {
+        self.pos
+    }
+}
+
+impl HasPos for Const {
+    fn pos(&self) -> Pos {
+        self.pos
+    }
+}
+
 impl HasPos for Shape {
     fn pos(&self) -> Pos {
         self.pos
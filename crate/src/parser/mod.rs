@@ -11,7 +11,11 @@ use ast::*;
 use error::ParseErrorType::*;
 use error::*;
 
-const RESERVED: &'static [&'static str] = &["shape", "transform"];
+// `transform` is not reserved as the transform blocks are builtins named
+// `translate`, `rotate`, `scale` and `skew`, and shapes take it as an arg
+const RESERVED: &[&str] = &[
+    "shape", "true", "false", "if", "else", "for", "in", "let", "const", "fn",
+];
 
 struct Parser<'a> {
     input: Peekable<Iter<'a, Token>>,
@@ -42,6 +46,9 @@ impl Token {
     // null denotation
     fn nud(&self, parser: &mut Parser) -> ParseResult<Expr> {
         match self.token_type() {
+            TokenType::Ident(ref s) if s == "true" || s == "false" => {
+                Ok(Expr::Literal(Literal::Bool(s == "true"), self.token_pos()))
+            }
             TokenType::Ident(s) => {
                 if is_reserved_word(s.as_str()) {
                     parse_error(IdentiferCannotBeReservedWord(s), self.token_pos().start)
@@ -50,13 +57,50 @@ impl Token {
                 }
             }
             TokenType::String(s) => Ok(Expr::Literal(Literal::String(s), self.token_pos())),
-            TokenType::Number(n) => Ok(Expr::Literal(Literal::Number(n), self.token_pos())),
+            TokenType::InterpolatedString(parts) => {
+                let parts = parts
+                    .iter()
+                    .map(parse_string_part)
+                    .collect::<ParseResult<Vec<InterpolatedPart>>>()?;
+                Ok(Expr::Interpolated(parts, self.token_pos()))
+            }
+            TokenType::Number(n, unit) => {
+                Ok(Expr::Literal(Literal::Number(n, unit), self.token_pos()))
+            }
+            TokenType::Color(c) => Ok(Expr::Literal(Literal::Color(c), self.token_pos())),
             TokenType::Minus => {
                 let e = parser.expression(prec(Precedence::Prefix))?;
                 Ok(Expr::Unary(UnOp::Neg, Box::new(e), self.token_pos().start))
             }
+            TokenType::Bang => {
+                let e = parser.expression(prec(Precedence::Prefix))?;
+                Ok(Expr::Unary(UnOp::Not, Box::new(e), self.token_pos().start))
+            }
+            TokenType::LCurly => parser.parse_record(self.token_pos().start),
+            TokenType::LBracket => {
+                let (items, end) = parser.parse_list(TokenType::RBracket, "']' to close list")?;
+                Ok(Expr::List(items, create_range(self.token_pos().start, end)))
+            }
             TokenType::LParen => {
                 let e = parser.expression(0)?;
+                if parser.match_next(TokenType::Comma).is_some() {
+                    // `(x, y)` is a vector
+                    let y = parser.expression(0)?;
+                    let token = parser.consume();
+                    return match token.as_ref().map(|t| t.token_type()) {
+                        Some(TokenType::RParen) => Ok(Expr::Tuple(
+                            Box::new(e),
+                            Box::new(y),
+                            create_range(self.token_pos().start, token.unwrap().token_pos().end),
+                        )),
+                        Some(t) => parse_error(
+                            Expected("')' to close vector".to_owned(), Some(format!("{:?}", t))),
+                            token.unwrap().token_pos().start,
+                        ),
+                        None => parse_error(UnExpectedEndOfInput, parser.input_end_pos),
+                    };
+                }
+
                 if let Some(TokenType::RParen) = parser.input.next().map(|t| t.token_type()) {
                     Ok(Expr::Grouping(Box::new(e)))
                 } else {
@@ -73,7 +117,30 @@ impl Token {
     // left denotation
     fn led(&self, parser: &mut Parser, lhs: Expr) -> ParseResult<Expr> {
         match self.token_type() {
-            TokenType::Times | TokenType::Divide | TokenType::Plus | TokenType::Minus => {
+            // right associative, so the right hand side binds at a lower precedence
+            TokenType::Power => {
+                let rhs = parser.expression(self.lbp() - 1)?;
+                Ok(Expr::Binary(
+                    Box::new(lhs),
+                    BinOp::Pow,
+                    Box::new(rhs),
+                    self.token_pos().start,
+                ))
+            }
+            TokenType::Times
+            | TokenType::Divide
+            | TokenType::IntDivide
+            | TokenType::Modulo
+            | TokenType::Plus
+            | TokenType::Minus
+            | TokenType::Compare
+            | TokenType::NotEqual
+            | TokenType::Less
+            | TokenType::LessEqual
+            | TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::And
+            | TokenType::Or => {
                 let rhs = parser.expression(self.lbp())?;
                 let op = token_to_binop(self.clone()).unwrap();
                 Ok(Expr::Binary(
@@ -83,6 +150,78 @@ impl Token {
                     self.token_pos().start,
                 ))
             }
+            // right associative, so `a ? b : c ? d : e` nests in the else branch
+            TokenType::Question => {
+                let then_expr = parser.expression(0)?;
+                if parser.match_next(TokenType::Colon).is_none() {
+                    return parse_error(
+                        Expected("':' in conditional expression".to_owned(), None),
+                        then_expr.pos(),
+                    );
+                }
+
+                let else_expr = parser.expression(self.lbp() - 1)?;
+                Ok(Expr::Conditional(
+                    Box::new(lhs),
+                    Box::new(then_expr),
+                    Box::new(else_expr),
+                    self.token_pos().start,
+                ))
+            }
+            // `step` is only a keyword directly after the end of a range
+            TokenType::DotDot => {
+                let end = parser.expression(self.lbp())?;
+                let step = if parser.next_is_word("step") {
+                    parser.consume();
+                    Some(Box::new(parser.expression(self.lbp())?))
+                } else {
+                    None
+                };
+
+                Ok(Expr::Range(
+                    Box::new(lhs),
+                    Box::new(end),
+                    step,
+                    self.token_pos().start,
+                ))
+            }
+            TokenType::Dot => match parser.consume() {
+                Some(token) => match token.token_type() {
+                    TokenType::Ident(name) => {
+                        Ok(Expr::Field(Box::new(lhs), name, token.token_pos().start))
+                    }
+                    t => parse_error(
+                        Expected("field name after '.'".to_owned(), Some(format!("{:?}", t))),
+                        token.token_pos().start,
+                    ),
+                },
+                None => parse_error(UnExpectedEndOfInput, parser.input_end_pos),
+            },
+            TokenType::LBracket => {
+                let index = parser.expression(0)?;
+                match parser.consume().map(|t| t.token_type()) {
+                    Some(TokenType::RBracket) => Ok(Expr::Index(
+                        Box::new(lhs),
+                        Box::new(index),
+                        self.token_pos().start,
+                    )),
+                    _ => parse_error(
+                        Expected("']' to close index".to_owned(), None),
+                        self.token_pos().start,
+                    ),
+                }
+            }
+            TokenType::LParen => match lhs {
+                Expr::Name(name, range) => {
+                    let (args, end) =
+                        parser.parse_list(TokenType::RParen, "')' to close function call")?;
+                    Ok(Expr::Call(name, args, create_range(range.start, end)))
+                }
+                e => parse_error(
+                    Expected("function name before '('".to_owned(), None),
+                    e.pos(),
+                ),
+            },
             t => parse_error(
                 Expected("operator".to_owned(), Some(format!("{:?}", t))),
                 self.token_pos().start,
@@ -93,20 +232,65 @@ impl Token {
     // left binding power
     fn lbp(&self) -> u32 {
         match self.token_type() {
-            TokenType::LParen => prec(Precedence::Call),
-            TokenType::Times | TokenType::Divide => prec(Precedence::Product),
+            TokenType::LParen | TokenType::LBracket | TokenType::Dot => prec(Precedence::Call),
+            TokenType::Power => prec(Precedence::Exponent),
+            TokenType::Times | TokenType::Divide | TokenType::IntDivide | TokenType::Modulo => {
+                prec(Precedence::Product)
+            }
             TokenType::Plus | TokenType::Minus => prec(Precedence::Sum),
+            TokenType::Less
+            | TokenType::LessEqual
+            | TokenType::Greater
+            | TokenType::GreaterEqual => prec(Precedence::Comparison),
+            TokenType::Compare | TokenType::NotEqual => prec(Precedence::Equality),
+            TokenType::And => prec(Precedence::And),
+            TokenType::Or => prec(Precedence::Or),
+            TokenType::Question => prec(Precedence::Conditional),
+            TokenType::DotDot => prec(Precedence::Range),
             _ => 0,
         }
     }
 }
 
+fn parse_string_part(part: &StringPart) -> ParseResult<InterpolatedPart> {
+    match part {
+        StringPart::Literal(s) => Ok(InterpolatedPart::Literal(s.clone())),
+        StringPart::Expr(tokens, range) => {
+            let mut parser = Parser::new(tokens);
+            parser.input_end_pos = range.end;
+
+            let expr = parser.expression(0)?;
+            match parser.input.next() {
+                None => Ok(InterpolatedPart::Expr(expr)),
+                Some(t) => parse_error(
+                    Expected(
+                        "'}' to close interpolation".to_owned(),
+                        Some(format!("{:?}", t.token_type())),
+                    ),
+                    t.pos(),
+                ),
+            }
+        }
+    }
+}
+
 fn token_to_binop(token: Token) -> Result<BinOp, String> {
     match token.token_type() {
         TokenType::Times => Ok(BinOp::Mul),
         TokenType::Divide => Ok(BinOp::Div),
+        TokenType::IntDivide => Ok(BinOp::IntDiv),
+        TokenType::Modulo => Ok(BinOp::Mod),
+        TokenType::Power => Ok(BinOp::Pow),
         TokenType::Plus => Ok(BinOp::Add),
         TokenType::Minus => Ok(BinOp::Sub),
+        TokenType::Compare => Ok(BinOp::Eq),
+        TokenType::NotEqual => Ok(BinOp::NotEq),
+        TokenType::Less => Ok(BinOp::Lt),
+        TokenType::LessEqual => Ok(BinOp::LtEq),
+        TokenType::Greater => Ok(BinOp::Gt),
+        TokenType::GreaterEqual => Ok(BinOp::GtEq),
+        TokenType::And => Ok(BinOp::And),
+        TokenType::Or => Ok(BinOp::Or),
         _ => Err("no binop for token".to_owned()),
     }
 }
@@ -115,15 +299,21 @@ impl<'a> Parser<'a> {
     pub fn new(input: &'a Vec<Token>) -> Parser<'a> {
         Parser {
             input: input.iter().peekable(),
-            input_end_pos: input[input.len() - 1].token_pos().end,
+            input_end_pos: input.last().map_or(create_pos(0, 0), |t| t.token_pos().end),
         }
     }
 
     pub fn statement(&mut self) -> ParseResult<Stmt> {
-        let expr = self.expression(0)?;
-        let pos = expr.pos();
-
-        Ok(Stmt::Expr(expr, pos))
+        if self.next_is_word("if") {
+            self.parse_if()
+        } else if self.next_is_word("for") {
+            self.parse_for()
+        } else if self.next_is_word("let") {
+            self.parse_let()
+        } else {
+            let call = self.parse_function_call()?;
+            Ok(Stmt::Call(call))
+        }
     }
 
     pub fn expression(&mut self, rbp: u32) -> ParseResult<Expr> {
@@ -150,8 +340,56 @@ impl<'a> Parser<'a> {
     }
 
     pub fn decl(&mut self) -> ParseResult<Decl> {
-        let shape = self.shape()?;
-        Ok(Decl::ShapeDecl(shape))
+        if self.next_is_word("const") {
+            let constant = self.parse_const()?;
+            Ok(Decl::ConstDecl(constant))
+        } else if self.next_is_word("fn") {
+            let function = self.function()?;
+            Ok(Decl::FnDecl(function))
+        } else if self.next_is_word("seed") {
+            // `seed` is only a keyword at the start of a declaration
+            let start = self.parse_reserved_word("seed")?;
+            let expr = self.expression(0)?;
+            Ok(Decl::SeedDecl(expr, start))
+        } else if self.next_is_word("layer") {
+            let start = self.parse_reserved_word("layer")?;
+            let (name, _) = self.parse_ident()?;
+            let block = self.parse_block()?;
+            Ok(Decl::LayerDecl(Layer {
+                name,
+                block,
+                pos: start,
+            }))
+        } else if self.next_is_word("canvas") {
+            let start = self.parse_reserved_word("canvas")?;
+            match self.match_next(TokenType::LCurly) {
+                Some(token) => {
+                    let record = self.parse_record(token.token_pos().start)?;
+                    Ok(Decl::CanvasDecl(record, start))
+                }
+                None => parse_error(Expected("'{' after canvas".to_owned(), None), start),
+            }
+        } else {
+            let shape = self.shape()?;
+            Ok(Decl::ShapeDecl(shape))
+        }
+    }
+
+    pub fn parse_const(&mut self) -> ParseResult<Const> {
+        let start = self.parse_reserved_word("const")?;
+        let (name, name_pos) = self.parse_ident()?;
+
+        if self.match_next(TokenType::Equals).is_none() {
+            return parse_error(Expected("'=' after const name".to_owned(), None), name_pos);
+        }
+
+        let expr = self.expression(0)?;
+
+        Ok(Const {
+            name,
+            expr,
+            pos: start,
+        })
     }
 
     pub fn parse_arg(&mut self) -> ParseResult<Arg> {
@@ -183,9 +421,27 @@ impl<'a> Parser<'a> {
         // shape name
         let (ident, ident_pos) = self.parse_ident()?;
 
+        let args = self.parse_arg_list("shape", ident_pos)?;
+
+        // block
+        let block = self.parse_block()?;
+
+        Ok(Shape {
+            name: ident,
+            args: args,
+            block: block,
+            pos: start,
+        })
+    }
+
+    // the parenthesized args of a shape or function declaration
+    fn parse_arg_list(&mut self, kind: &str, ident_pos: Pos) -> ParseResult<Vec<Arg>> {
         // left paren
-        if let None = self.match_next(TokenType::LParen) {
-            return parse_error(Expected("'(' after shape name".to_owned(), None), ident_pos);
+        if self.match_next(TokenType::LParen).is_none() {
+            return parse_error(
+                Expected(format!("'(' after {} name", kind), None),
+                ident_pos,
+            );
         }
 
         // args list
@@ -195,7 +451,7 @@ impl<'a> Parser<'a> {
             let arg = self.parse_arg()?;
             args.push(arg);
 
-            while let Some(_) = self.match_next(TokenType::Comma) {
+            while self.match_next(TokenType::Comma).is_some() {
                 let arg = self.parse_arg()?;
                 args.push(arg);
             }
@@ -205,26 +461,36 @@ impl<'a> Parser<'a> {
 
         // right paren
         match token.map(|t| t.token_type()) {
-            Some(TokenType::RParen) => (),
-            Some(t) => {
-                return parse_error(
-                    Expected(
-                        "expecting ')' to close function call.".to_owned(),
-                        Some(format!("{:?}", t)),
-                    ),
-                    token.unwrap().token_pos().start,
-                )
-            }
-            None => return parse_error(UnExpectedEndOfInput, self.input_end_pos),
-        };
+            Some(TokenType::RParen) => Ok(args),
+            Some(t) => parse_error(
+                Expected(
+                    "expecting ')' to close function call.".to_owned(),
+                    Some(format!("{:?}", t)),
+                ),
+                token.unwrap().token_pos().start,
+            ),
+            None => parse_error(UnExpectedEndOfInput, self.input_end_pos),
+        }
+    }
 
-        // block
-        let block = self.parse_block()?;
+    pub fn function(&mut self) -> ParseResult<Function> {
+        let start = self.parse_reserved_word("fn")?;
+        let (ident, ident_pos) = self.parse_ident()?;
+        let args = self.parse_arg_list("function", ident_pos)?;
 
-        Ok(Shape {
+        if self.match_next(TokenType::Equals).is_none() {
+            return parse_error(
+                Expected("'=' before function body".to_owned(), None),
+                ident_pos,
+            );
+        }
+
+        let expr = self.expression(0)?;
+
+        Ok(Function {
             name: ident,
-            args: args,
-            block: block,
+            args,
+            expr,
             pos: start,
         })
     }
@@ -283,13 +549,18 @@ impl<'a> Parser<'a> {
 
         // args
         let mut args: Vec<NamedArg> = vec![];
+        let mut spreads: Vec<Expr> = vec![];
         if self.next_token_type() != Some(TokenType::RParen) {
-            let mut arg = self.parse_named_arg()?;
-            args.push(arg);
+            loop {
+                if self.match_next(TokenType::Ellipsis).is_some() {
+                    spreads.push(self.expression(0)?);
+                } else {
+                    args.push(self.parse_named_arg()?);
+                }
 
-            while let Some(_) = self.match_next(TokenType::Comma) {
-                arg = self.parse_named_arg()?;
-                args.push(arg);
+                if self.match_next(TokenType::Comma).is_none() {
+                    break;
+                }
             }
         }
 
@@ -309,13 +580,103 @@ impl<'a> Parser<'a> {
             None => return parse_error(UnExpectedEndOfInput, ident_pos),
         }
 
+        let range = create_range(ident_pos, token.unwrap().token_pos().start);
+
+        let children = if self.next_token_type() == Some(TokenType::LCurly) {
+            Some(self.parse_block()?)
+        } else {
+            None
+        };
+
         Ok(FunCall {
             ident: ident,
             args: args,
-            range: create_range(ident_pos, token.unwrap().token_pos().start),
+            spreads,
+            children,
+            range,
         })
     }
 
+    // comma separated expressions up to the closing token, like the positional
+    // arguments of a call expression or the items of a list
+    fn parse_list(&mut self, close: TokenType, expected: &str) -> ParseResult<(Vec<Expr>, Pos)> {
+        let mut items: Vec<Expr> = vec![];
+
+        if self.next_token_type() != Some(close.clone()) {
+            items.push(self.expression(0)?);
+
+            while self.match_next(TokenType::Comma).is_some() {
+                // allow a trailing comma
+                if self.next_token_type() == Some(close.clone()) {
+                    break;
+                }
+                items.push(self.expression(0)?);
+            }
+        }
+
+        let token = self.consume();
+        match token.as_ref().map(|t| t.token_type()) {
+            Some(ref t) if *t == close => Ok((items, token.unwrap().token_pos().end)),
+            Some(t) => parse_error(
+                Expected(expected.to_owned(), Some(format!("{:?}", t))),
+                token.unwrap().token_pos().start,
+            ),
+            None => parse_error(UnExpectedEndOfInput, self.input_end_pos),
+        }
+    }
+
+    // a record literal, after the opening curly
+    fn parse_record(&mut self, start: Pos) -> ParseResult<Expr> {
+        let mut entries: Vec<RecordEntry> = vec![];
+
+        loop {
+            if let Some(token) = self.match_next(TokenType::RCurly) {
+                return Ok(Expr::Record(
+                    entries,
+                    create_range(start, token.token_pos().end),
+                ));
+            }
+
+            if self.match_next(TokenType::Ellipsis).is_some() {
+                entries.push(RecordEntry::Spread(self.expression(0)?));
+            } else {
+                // keys which are not identifiers, like `stroke-width`, are strings
+                let token = match self.consume() {
+                    Some(token) => token,
+                    None => return parse_error(UnExpectedEndOfInput, self.input_end_pos),
+                };
+                let key = match token.token_type() {
+                    TokenType::Ident(s) | TokenType::String(s) => s,
+                    t => {
+                        return parse_error(
+                            Expected("record field name".to_owned(), Some(format!("{:?}", t))),
+                            token.token_pos().start,
+                        )
+                    }
+                };
+
+                if self.match_next(TokenType::Colon).is_none() {
+                    return parse_error(
+                        Expected("':' after record field name".to_owned(), None),
+                        token.token_pos().end,
+                    );
+                }
+
+                entries.push(RecordEntry::Field(key, self.expression(0)?));
+            }
+
+            if self.match_next(TokenType::Comma).is_none() {
+                return match self.match_next(TokenType::RCurly) {
+                    Some(token) => Ok(Expr::Record(
+                        entries,
+                        create_range(start, token.token_pos().end),
+                    )),
+                    None => parse_error(Expected("'}' to close record".to_owned(), None), start),
+                };
+            }
+        }
+    }
+
     pub fn parse_block(&mut self) -> ParseResult<Block> {
         // left curly
         let token = self.input.next();
@@ -330,19 +691,19 @@ impl<'a> Parser<'a> {
             None => return parse_error(UnExpectedEndOfInput, self.input_end_pos),
         }
 
-        let mut calls: Vec<FunCall> = vec![];
+        let mut stmts: Vec<Stmt> = vec![];
 
         let mut last = self.match_next(TokenType::RCurly);
         while let None = last {
-            let call = self.parse_function_call()?;
-            calls.push(call);
+            let stmt = self.statement()?;
+            stmts.push(stmt);
 
             last = self.match_next(TokenType::RCurly);
         }
 
         match &last {
             Some(_) => Ok(Block {
-                calls: calls,
+                stmts,
                 range: create_range(
                     token.unwrap().token_pos().start,
                     last.unwrap().token_pos().end,
@@ -355,6 +716,68 @@ impl<'a> Parser<'a> {
         }
     }
 
+    pub fn parse_if(&mut self) -> ParseResult<Stmt> {
+        let start = self.parse_reserved_word("if")?;
+        let cond = self.expression(0)?;
+        let then_block = self.parse_block()?;
+        let mut end = then_block.range.end;
+
+        let else_block = if self.next_is_word("else") {
+            self.consume();
+
+            let block = if self.next_is_word("if") {
+                let stmt = self.parse_if()?;
+                let range = match &stmt {
+                    Stmt::If(_, _, _, range) => *range,
+                    _ => unreachable!(),
+                };
+
+                Block {
+                    stmts: vec![stmt],
+                    range,
+                }
+            } else {
+                self.parse_block()?
+            };
+
+            end = block.range.end;
+            Some(block)
+        } else {
+            None
+        };
+
+        Ok(Stmt::If(
+            cond,
+            then_block,
+            else_block,
+            create_range(start, end),
+        ))
+    }
+
+    pub fn parse_for(&mut self) -> ParseResult<Stmt> {
+        let start = self.parse_reserved_word("for")?;
+        let (name, _) = self.parse_ident()?;
+        self.parse_reserved_word("in")?;
+        let iter = self.expression(0)?;
+        let block = self.parse_block()?;
+        let end = block.range.end;
+
+        Ok(Stmt::For(name, iter, block, create_range(start, end)))
+    }
+
+    pub fn parse_let(&mut self) -> ParseResult<Stmt> {
+        let start = self.parse_reserved_word("let")?;
+        let (name, name_pos) = self.parse_ident()?;
+
+        if self.match_next(TokenType::Equals).is_none() {
+            return parse_error(Expected("'=' after let name".to_owned(), None), name_pos);
+        }
+
+        let expr = self.expression(0)?;
+
+        Ok(Stmt::Let(name, expr, start))
+    }
+
     pub fn parse_named_arg(&mut self) -> ParseResult<NamedArg> {
         let name = match self.parse_ident() {
             Err(err) => return parse_error(Expected("argument name".to_owned(), None), err.pos()),
@@ -388,6 +811,13 @@ impl<'a> Parser<'a> {
         }
     }
 
+    fn next_is_word(&mut self, word: &str) -> bool {
+        match self.next_token_type() {
+            Some(TokenType::Ident(s)) => s == word,
+            _ => false,
+        }
+    }
+
     pub fn next_token_type(&mut self) -> Option<TokenType> {
         self.input.peek().map(|t| t.token_type())
     }
@@ -461,6 +891,115 @@ mod tests {
         assert_debug_snapshot_matches!(ast);
     }
 
+    #[test]
+    fn parse_logical_precedence() {
+        let ast = parse_expression(&"!a || b + 1 < c && d == true".to_owned());
+        assert_debug_snapshot_matches!(ast);
+    }
+
+    #[test]
+    fn parse_exponent_associativity() {
+        let ast = parse_expression(&"2 ** 3 ** 2 * 4 % 3".to_owned());
+        assert_debug_snapshot_matches!(ast);
+
+        // `-2 ** 2` is `-(2 ** 2)`
+        let ast = parse_expression(&"-2 ** 2".to_owned());
+        assert!(
+            matches!(&ast, Ok(Expr::Unary(UnOp::Neg, e, _)) if matches!(**e, Expr::Binary(_, BinOp::Pow, _, _))),
+            "{:?}",
+            ast
+        );
+    }
+
+    #[test]
+    fn parse_call_expression() {
+        let ast = parse_expression(&"mix(#fff, darken(c, 10%), 0.5)".to_owned());
+        assert_debug_snapshot_matches!(ast);
+    }
+
+    #[test]
+    fn parse_list_and_index() {
+        let ast = parse_expression(&"[1, [a, b,]][0][i + 1]".to_owned());
+        assert_debug_snapshot_matches!(ast);
+    }
+
+    #[test]
+    fn parse_unclosed_list() {
+        let ast = parse_expression(&"[1, 2".to_owned());
+        assert_eq!(ast.map_err(|e| e.error_type), Err(UnExpectedEndOfInput));
+
+        let ast = parse_expression(&"xs[1".to_owned());
+        assert_eq!(ast.map_err(|e| e.pos), Err(create_pos(0, 2)));
+    }
+
+    #[test]
+    fn parse_record_and_field() {
+        let ast = parse_expression(&"{x: 1, \"stroke-width\": 2, ...base,}.x".to_owned());
+        assert_debug_snapshot_matches!(ast);
+    }
+
+    #[test]
+    fn parse_record_errors() {
+        let ast = parse_expression(&"{x 1}".to_owned());
+        assert_eq!(ast.map_err(|e| e.pos), Err(create_pos(0, 2)));
+
+        let ast = parse_expression(&"{x: 1".to_owned());
+        assert_eq!(ast.map_err(|e| e.pos), Err(create_pos(0, 0)));
+
+        let ast = parse_expression(&"p.\"x\"".to_owned());
+        assert_eq!(ast.map_err(|e| e.pos), Err(create_pos(0, 2)));
+    }
+
+    #[test]
+    fn parse_vector() {
+        let ast = parse_expression(&"(1, y + 2) * (3)".to_owned());
+        assert_debug_snapshot_matches!(ast);
+
+        let ast = parse_expression(&"(1, 2, 3)".to_owned());
+        assert_eq!(ast.map_err(|e| e.pos), Err(create_pos(0, 5)));
+    }
+
+    #[test]
+    fn parse_call_children() {
+        let code = "shape s() {
+  element(name: \"g\") {
+    circle()
+  }
+}";
+
+        let ast = parse_shape(&code.to_owned());
+        assert_debug_snapshot_matches!(ast);
+    }
+
+    #[test]
+    fn parse_call_spread() {
+        let code = "shape s(style) {
+  circle(...style, r: 10)
+}";
+
+        let ast = parse_shape(&code.to_owned());
+        assert_debug_snapshot_matches!(ast);
+    }
+
+    #[test]
+    fn parse_interpolated_string() {
+        let ast = parse_expression(&"\"cx=${cx * 2}px\"".to_owned());
+        assert_debug_snapshot_matches!(ast);
+    }
+
+    #[test]
+    fn parse_interpolation_error_position() {
+        let ast = parse_expression(&"\"a\n  ${1 +}\"".to_owned());
+        assert_eq!(
+            ast.map_err(|e| e.pos),
+            Err(create_pos(1, 8)),
+            "error should point at the end of the interpolation"
+        );
+
+        let ast = parse_expression(&"\"${a b}\"".to_owned());
+        assert_eq!(ast.map_err(|e| e.pos), Err(create_pos(0, 5)));
+    }
+
     #[test]
     fn parse_simple_shape() {
         let code = "shape circle(r) {
@@ -496,6 +1035,129 @@ mod tests {
         assert_debug_snapshot_matches!(ast);
     }
 
+    #[test]
+    fn parse_conditional_expression() {
+        let ast = parse_expression(&"a ? 1 : b || c ? 2 : 3".to_owned());
+        assert_debug_snapshot_matches!(ast);
+    }
+
+    #[test]
+    fn parse_conditional_missing_colon() {
+        let ast = parse_expression(&"a ? 1".to_owned());
+        assert_eq!(ast.map_err(|e| e.pos), Err(create_pos(0, 4)));
+    }
+
+    #[test]
+    fn parse_shape_if_statement() {
+        let code = "shape badge(outlined, big) {
+  if outlined {
+    circle(fill: \"none\")
+  } else if big {
+    circle(r: 20)
+  } else {
+    circle()
+  }
+  if big { rect() }
+}";
+
+        let ast = parse_shape(&code.to_owned());
+        assert_debug_snapshot_matches!(ast);
+    }
+
+    #[test]
+    fn parse_range_expression() {
+        let ast = parse_expression(&"0..n + 1 step 2 * s".to_owned());
+        assert_debug_snapshot_matches!(ast);
+    }
+
+    #[test]
+    fn parse_shape_for_statement() {
+        let code = "shape row(n) {
+  for i in 0..n step 2 {
+    circle(cx: i * 10)
+  }
+}";
+
+        let ast = parse_shape(&code.to_owned());
+        assert_debug_snapshot_matches!(ast);
+    }
+
+    #[test]
+    fn parse_shape_let_statement() {
+        let code = "shape badge(size) {
+  let r = size / 2
+  circle(r: r)
+}";
+
+        let ast = parse_shape(&code.to_owned());
+        assert_debug_snapshot_matches!(ast);
+    }
+
+    #[test]
+    fn parse_let_missing_equals() {
+        let ast = parse_shape(&"shape s() { let r 2 }".to_owned());
+        assert_eq!(ast.map_err(|e| e.pos), Err(create_pos(0, 16)));
+    }
+
+    #[test]
+    fn parse_let_reserved_name() {
+        let ast = parse_shape(&"shape main() { let let = 1 }".to_owned());
+        assert_eq!(
+            ast.map_err(|e| e.error_type),
+            Err(IdentiferCannotBeReservedWord("let".to_owned()))
+        );
+    }
+
+    #[test]
+    fn parse_const_reserved_name() {
+        let tokens = lexer::lex(&"const const = 1".to_owned()).unwrap();
+        assert_eq!(
+            parse_program(tokens).map_err(|e| e.error_type),
+            Err(IdentiferCannotBeReservedWord("const".to_owned()))
+        );
+    }
+
+    #[test]
+    fn parse_fn_reserved_param() {
+        let tokens = lexer::lex(&"fn double(fn) = fn * 2".to_owned()).unwrap();
+        assert_eq!(
+            parse_program(tokens).map_err(|e| e.error_type),
+            Err(IdentiferCannotBeReservedWord("fn".to_owned()))
+        );
+    }
+
+    #[test]
+    fn parse_transform_arg_name() {
+        let code = "shape s(transform = false) {
+  g(transform: transform)
+}";
+
+        let shape = parse_shape(&code.to_owned()).unwrap();
+        assert_eq!(shape.args[0].name, "transform");
+    }
+
+    #[test]
+    fn parse_dangling_else() {
+        let code = "shape s() {
+  else { circle() }
+}";
+
+        let ast = parse_shape(&code.to_owned());
+        assert_eq!(
+            ast.map_err(|e| e.error_type),
+            Err(IdentiferCannotBeReservedWord("else".to_owned()))
+        );
+    }
+
+    #[test]
+    fn parse_function_decl() {
+        let code = "fn polar_x(r, a = 0deg) = r * cos(a)";
+
+        let tokens = lexer::lex(&code.to_owned()).unwrap();
+        let program = parse_program(tokens);
+        assert_debug_snapshot_matches!(program);
+    }
+
     #[test]
     fn parse_simple_program() {
         let code = "
@@ -514,4 +1176,57 @@ shape shape2(r) {
         assert_eq!(2, program.decls.len());
         assert_debug_snapshot_matches!(program);
     }
+
+    #[test]
+    fn parse_program_with_consts() {
+        let code = "
+const SIZE = 10
+const PINK = #ff69b4
+
+shape main() {
+  circle(r: SIZE, fill: PINK)
+}
+";
+
+        let tokens = lexer::lex(&code.to_owned()).unwrap();
+        let program = parse_program(tokens).unwrap();
+
+        assert_eq!(3, program.decls.len());
+        assert_debug_snapshot_matches!(program);
+    }
+
+    #[test]
+    fn parse_program_with_canvas() {
+        let code = "
+canvas { width: 400, height: 300, background: #fff }
+
+shape main() {}
+";
+
+        let tokens = lexer::lex(&code.to_owned()).unwrap();
+        let program = parse_program(tokens).unwrap();
+
+        assert_eq!(2, program.decls.len());
+        assert_debug_snapshot_matches!(program);
+
+        let tokens = lexer::lex(&"canvas 400".to_owned()).unwrap();
+        assert_eq!(
+            parse_program(tokens).map_err(|e| e.pos),
+            Err(create_pos(0, 0))
+        );
+    }
+
+    #[test]
+    fn parse_program_with_layer() {
+        let code = "
+layer background {
+  rect()
+}
+";
+
+        let tokens = lexer::lex(&code.to_owned()).unwrap();
+        let program = parse_program(tokens).unwrap();
+
+        assert_debug_snapshot_matches!(program);
+    }
 }
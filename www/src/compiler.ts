@@ -4,6 +4,18 @@ export interface CompileError {
   message: string;
 }
 
+export interface CompileOptions {
+  seed?: number;
+  // allows raw_svg(...) markup, only for trusted code
+  rawSvg?: boolean;
+  // override the canvas declared by the program, e.g. to export at a size
+  width?: number;
+  height?: number;
+  viewBox?: [number, number, number, number];
+  background?: string;
+  units?: "px" | "mm" | "cm" | "in" | "pt" | "pc";
+}
+
 export interface CompileResult {
   svg?: string;
   error?: CompileError;
@@ -21,9 +33,42 @@ const loadModule = (): Promise<typeof import("../../crate/pkg")> => {
   return import("../../crate/pkg");
 };
 
-export const compile = async (value: string): Promise<CompileResult> => {
+export const compile = async (
+  value: string,
+  options: CompileOptions = {}
+): Promise<CompileResult> => {
   const mod = await loadModule();
-  const result = mod.compile(value);
+
+  // wasm objects are not garbage collected, so the options are freed below
+  const compileOptions = new mod.CompileOptions();
+  if (options.seed != null) {
+    compileOptions.set_seed(options.seed);
+  }
+  if (options.rawSvg) {
+    compileOptions.set_raw_svg(true);
+  }
+  if (options.width != null) {
+    compileOptions.set_width(options.width);
+  }
+  if (options.height != null) {
+    compileOptions.set_height(options.height);
+  }
+  if (options.viewBox != null) {
+    compileOptions.set_view_box(...options.viewBox);
+  }
+  if (options.background != null) {
+    compileOptions.set_background(options.background);
+  }
+  if (options.units != null) {
+    compileOptions.set_units(options.units);
+  }
+
+  let result;
+  try {
+    result = mod.compile_with_options(value, compileOptions);
+  } finally {
+    compileOptions.free();
+  }
 
   const error = result.get_error();
   const compileResult: CompileResult = {